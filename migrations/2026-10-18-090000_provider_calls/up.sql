@@ -0,0 +1,6 @@
+CREATE TABLE provider_calls (
+    provider TINYINT UNSIGNED NOT NULL,
+    day DATE NOT NULL,
+    calls INT UNSIGNED NOT NULL,
+    PRIMARY KEY (provider, day)
+);
@@ -0,0 +1 @@
+ALTER TABLE covers ADD COLUMN failure VARCHAR(255);
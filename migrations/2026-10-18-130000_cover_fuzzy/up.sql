@@ -0,0 +1 @@
+ALTER TABLE covers ADD COLUMN fuzzy BOOLEAN NOT NULL DEFAULT FALSE;
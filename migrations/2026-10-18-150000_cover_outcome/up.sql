@@ -0,0 +1 @@
+ALTER TABLE covers ADD COLUMN outcome TINYINT UNSIGNED;
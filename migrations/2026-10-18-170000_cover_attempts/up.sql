@@ -0,0 +1,14 @@
+CREATE TABLE cover_attempts (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    product_id INT(11) UNSIGNED NOT NULL,
+    provider VARCHAR(255) NOT NULL,
+    barcode VARCHAR(32) NOT NULL,
+    tried_at DATETIME NOT NULL,
+    http_status SMALLINT UNSIGNED,
+    outcome TINYINT UNSIGNED NOT NULL,
+    error TEXT,
+    duration_ms INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    INDEX (product_id),
+    INDEX (tried_at)
+);
@@ -0,0 +1 @@
+ALTER TABLE covers DROP COLUMN attempts, DROP COLUMN next_try_at;
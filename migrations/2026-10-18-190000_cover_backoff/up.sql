@@ -0,0 +1,5 @@
+ALTER TABLE covers ADD COLUMN attempts INT UNSIGNED NOT NULL DEFAULT 0, ADD COLUMN next_try_at DATETIME;
+-- one-time backfill of the products without cover, which waited wait_seconds_retry_retrieve_cover after last_try.
+-- A migration can not read the configuration, the default of one hour is used; with another value next_try_at must be updated by hand.
+UPDATE covers SET attempts = 1, next_try_at = DATE_ADD(last_try, INTERVAL 1 HOUR) WHERE provider IS NULL;
+CREATE INDEX covers_next_try_at ON covers (next_try_at);
@@ -0,0 +1 @@
+ALTER TABLE covers DROP COLUMN original_type, DROP COLUMN original_width, DROP COLUMN original_height;
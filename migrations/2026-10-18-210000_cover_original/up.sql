@@ -0,0 +1 @@
+ALTER TABLE covers ADD COLUMN original_type VARCHAR(64), ADD COLUMN original_width INT UNSIGNED, ADD COLUMN original_height INT UNSIGNED;
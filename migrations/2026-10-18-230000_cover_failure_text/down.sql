@@ -0,0 +1,2 @@
+UPDATE covers SET failure = LEFT(failure, 255);
+ALTER TABLE covers MODIFY failure VARCHAR(255);
@@ -0,0 +1 @@
+ALTER TABLE covers MODIFY failure TEXT;
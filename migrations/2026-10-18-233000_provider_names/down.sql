@@ -0,0 +1,16 @@
+ALTER TABLE covers DROP COLUMN provider_name;
+CREATE TABLE provider_calls_codes (
+    provider TINYINT UNSIGNED NOT NULL,
+    day DATE NOT NULL,
+    calls INT UNSIGNED NOT NULL,
+    PRIMARY KEY (provider, day)
+);
+INSERT INTO provider_calls_codes
+SELECT CASE
+        WHEN provider LIKE 'Template %' THEN 5
+        WHEN provider LIKE 'Directory %' THEN 6
+        ELSE FIELD(provider, 'OpenLibrary', 'Manual', 'GoogleBooks', 'InternetArchive', 'Isbndb', 'Template', 'Directory', 'MusicBrainz') - 1
+    END AS code, day, SUM(calls)
+FROM provider_calls GROUP BY code, day;
+DROP TABLE provider_calls;
+RENAME TABLE provider_calls_codes TO provider_calls;
@@ -0,0 +1,3 @@
+ALTER TABLE covers ADD COLUMN provider_name VARCHAR(255);
+ALTER TABLE provider_calls MODIFY provider VARCHAR(255) NOT NULL;
+UPDATE provider_calls SET provider = ELT(provider + 1, 'OpenLibrary', 'Manual', 'GoogleBooks', 'InternetArchive', 'Isbndb', 'Template', 'Directory', 'MusicBrainz');
@@ -1,25 +1,285 @@
+use std::{
+    io::ErrorKind,
+    path::{Path as FsPath, PathBuf},
+};
+
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE, VARY},
+        HeaderMap, HeaderValue,
+    },
     response::IntoResponse,
 };
+use serde::Deserialize;
+use tokio::task::spawn_blocking;
+use tracing::warn;
+
+use crate::{
+    error::AppError,
+    image::{
+        original_path, render, resized_path, size_or_largest, write_resized, CoverSize, Dimension,
+        Encoding, Fit, Format,
+    },
+    AppState,
+};
 
-use crate::{error::AppError, AppState};
+#[derive(Deserialize)]
+pub struct FormatParams {
+    format: Option<Format>,
+}
 
 pub async fn get_cover(
-    Path(id): Path<u32>,
-    Path(size): Path<String>,
+    Path((id, size)): Path<(u32, String)>,
+    Query(params): Query<FormatParams>,
+    headers: HeaderMap,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
-    let mut path_cover = state.config.path_covers.to_owned();
-    path_cover.push(format!("{id}-{size}.webp"));
-    Ok(tokio::fs::read(path_cover).await?)
+    // an unknown size gives the largest one.
+    let size = size_or_largest(&state.config.sizes, &size).ok_or(AppError::SizeUnknown)?;
+    let encoding = negotiate(&state.config.formats, params.format, headers.get(ACCEPT))?;
+    let path_cover = state.config.path_covers.join(size.file_name(id));
+    // covers are stored in WebP, other formats are rendered from the original.
+    if encoding.format() == Format::WebP {
+        return Ok(served(encoding, tokio::fs::read(path_cover).await?));
+    }
+    let path = resized_path(
+        &state.config.path_covers,
+        &format!("{id}-{}", size.name),
+        encoding.format(),
+    );
+    if let Some(cover) = read_kept(&path).await? {
+        return Ok(served(encoding, cover));
+    }
+    let source = read_original_or(&state.config.path_covers, id, path_cover).await?;
+    let cover = render_and_keep(path, source, size.clone(), encoding).await?;
+    Ok(served(encoding, cover))
 }
 
 pub async fn get_default_cover(
     Path(size): Path<String>,
+    Query(params): Query<FormatParams>,
+    headers: HeaderMap,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
+    let size = size_or_largest(&state.config.sizes, &size).ok_or(AppError::SizeUnknown)?;
+    let encoding = negotiate(&state.config.formats, params.format, headers.get(ACCEPT))?;
     let mut path_cover = state.config.path_covers.to_owned();
-    path_cover.push(format!("cover-default-{size}.webp"));
-    Ok(tokio::fs::read(path_cover).await?)
+    path_cover.push(format!("cover-default-{}.webp", size.name));
+    if encoding.format() == Format::WebP {
+        return Ok(served(encoding, tokio::fs::read(path_cover).await?));
+    }
+    // the default covers have no original, they are converted from WebP.
+    let path = resized_path(
+        &state.config.path_covers,
+        &format!("default-{}", size.name),
+        encoding.format(),
+    );
+    if let Some(cover) = read_kept(&path).await? {
+        return Ok(served(encoding, cover));
+    }
+    let source = tokio::fs::read(path_cover).await?;
+    let cover = render_and_keep(path, source, size.clone(), encoding).await?;
+    Ok(served(encoding, cover))
+}
+
+#[derive(Deserialize)]
+pub struct ResizeParams {
+    w: u32,
+    h: u32,
+    fit: Option<Fit>,
+    format: Option<Format>,
+}
+
+/// cover resized on demand from the original, rendered once and then read from disk.
+pub async fn get_resized_cover(
+    Path(id): Path<u32>,
+    Query(params): Query<ResizeParams>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let dimension = Dimension {
+        width: params.w,
+        height: params.h,
+    };
+    // every dimension requested would be kept on disk.
+    if !state.config.resize_dimensions.contains(&dimension) {
+        return Err(AppError::DimensionNotAllowed);
+    }
+    let fit = params.fit.unwrap_or(Fit::Inside);
+    let encoding = negotiate(&state.config.formats, params.format, headers.get(ACCEPT))?;
+    let path = resized_path(
+        &state.config.path_covers,
+        &format!(
+            "{id}-{}x{}-{}",
+            dimension.width,
+            dimension.height,
+            fit.name()
+        ),
+        encoding.format(),
+    );
+    if let Some(cover) = read_kept(&path).await? {
+        return Ok(served(encoding, cover));
+    }
+    // covers written before originals were kept are resized from their largest size.
+    let largest = size_or_largest(&state.config.sizes, "").ok_or(AppError::SizeUnknown)?;
+    let source = read_original_or(
+        &state.config.path_covers,
+        id,
+        state.config.path_covers.join(largest.file_name(id)),
+    )
+    .await?;
+    let cover =
+        render_and_keep(path, source, CoverSize::on_demand(dimension, fit), encoding).await?;
+    Ok(served(encoding, cover))
+}
+
+/// settings of the format asked by the parameter, or else of the format preferred by the Accept header.
+/// The first format enabled is used if the client accepts none of them.
+fn negotiate(
+    formats: &[Encoding],
+    asked: Option<Format>,
+    accept: Option<&HeaderValue>,
+) -> Result<Encoding, AppError> {
+    if let Some(format) = asked {
+        return formats
+            .iter()
+            .find(|e| e.format() == format)
+            .copied()
+            .ok_or(AppError::FormatNotEnabled);
+    }
+    let accept = accept.and_then(|a| a.to_str().ok()).unwrap_or("*/*");
+    let mut preferred: Option<(f32, Encoding)> = None;
+    for encoding in formats {
+        let weight = accept_weight(accept, encoding.format().content_type());
+        // with the same weight, the order of the configuration is kept.
+        if weight > 0.0 && !preferred.is_some_and(|(w, _)| w >= weight) {
+            preferred = Some((weight, *encoding));
+        }
+    }
+    Ok(preferred.map_or(formats[0], |(_, e)| e))
+}
+
+/// quality given by the Accept header to the content type, from the most specific media range matching it.
+fn accept_weight(accept: &str, content_type: &str) -> f32 {
+    let kind = content_type.split('/').next().unwrap_or_default();
+    accept
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let media = params.next()?.trim();
+            let specificity = if media.eq_ignore_ascii_case(content_type) {
+                2
+            } else if media
+                .strip_suffix("/*")
+                .is_some_and(|k| k.eq_ignore_ascii_case(kind))
+            {
+                1
+            } else if media == "*/*" {
+                0
+            } else {
+                return None;
+            };
+            let quality = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            Some((specificity, quality))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map_or(0.0, |(_, quality)| quality)
+}
+
+/// response with the type of the cover, which depends on the Accept header.
+fn served(encoding: Encoding, cover: Vec<u8>) -> impl IntoResponse {
+    (
+        [
+            (CONTENT_TYPE, encoding.format().content_type()),
+            (VARY, "Accept"),
+        ],
+        cover,
+    )
+}
+
+/// cover rendered by a previous request.
+async fn read_kept(path: &FsPath) -> Result<Option<Vec<u8>>, AppError> {
+    match tokio::fs::read(path).await {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        kept => Ok(Some(kept?)),
+    }
+}
+
+/// original of the product, or the file given for covers written before originals were kept.
+async fn read_original_or(
+    path_covers: &FsPath,
+    id: u32,
+    fallback: PathBuf,
+) -> Result<Vec<u8>, AppError> {
+    match tokio::fs::read(original_path(path_covers, id)).await {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(tokio::fs::read(fallback).await?),
+        original => Ok(original?),
+    }
+}
+
+/// render the cover from the source and keep it on disk for the next requests.
+async fn render_and_keep(
+    path: PathBuf,
+    source: Vec<u8>,
+    size: CoverSize,
+    encoding: Encoding,
+) -> Result<Vec<u8>, AppError> {
+    spawn_blocking(move || {
+        let cover = render(&source, &size, encoding)?;
+        // the cover can still be served if it could not be kept.
+        if let Err(e) = write_resized(&path, &cover) {
+            warn!(
+                "rendered cover {} could not be written: {e}",
+                path.display()
+            );
+        }
+        anyhow::Ok(cover)
+    })
+    .await
+    .map_err(|e| AppError::Backend.transmit_error(&e))?
+    .map_err(|e| AppError::Backend.transmit_error(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiated(asked: Option<Format>, accept: Option<&'static str>) -> Option<Format> {
+        let accept = accept.map(HeaderValue::from_static);
+        negotiate(&Encoding::defaults(), asked, accept.as_ref())
+            .ok()
+            .map(|e| e.format())
+    }
+
+    #[test]
+    fn weight_of_the_most_specific_range() {
+        let accept = "image/avif,image/webp;q=0.9,image/*;q=0.8,*/*;q=0.5";
+        assert_eq!(accept_weight(accept, "image/webp"), 0.9);
+        assert_eq!(accept_weight(accept, "image/png"), 0.8);
+        assert_eq!(accept_weight("text/html,*/*;q=0.1", "image/png"), 0.1);
+        assert_eq!(accept_weight("text/html", "image/png"), 0.0);
+        assert_eq!(accept_weight("IMAGE/PNG", "image/png"), 1.0);
+        // a format refused explicitly is not accepted by a wider range.
+        assert_eq!(accept_weight("image/*, image/jpeg;q=0", "image/jpeg"), 0.0);
+        assert_eq!(accept_weight("image/png;q=high", "image/png"), 0.0);
+    }
+
+    #[test]
+    fn format_asked_or_preferred() {
+        assert!(negotiated(Some(Format::Png), Some("image/webp")) == Some(Format::Png));
+        assert!(negotiated(None, None) == Some(Format::WebP));
+        assert!(negotiated(None, Some("image/jpeg")) == Some(Format::Jpeg));
+        assert!(negotiated(None, Some("image/png;q=0.9,image/webp;q=0.5")) == Some(Format::Png));
+        // with the same weight, the first format of the configuration is kept.
+        assert!(negotiated(None, Some("image/png,image/jpeg")) == Some(Format::Jpeg));
+        assert!(negotiated(None, Some("text/html")) == Some(Format::WebP));
+        let formats = [Encoding::WebP { quality: None }];
+        assert!(matches!(
+            negotiate(&formats, Some(Format::Png), None),
+            Err(AppError::FormatNotEnabled)
+        ));
+    }
 }
@@ -1,11 +1,10 @@
 use anyhow::bail;
-use std::{fs::remove_file, path::PathBuf, sync::Arc};
-use strum::IntoEnumIterator;
+use std::{fs::remove_file, io::ErrorKind, path::Path as FsPath, sync::Arc, time::Duration};
 
 use axum::{
     body::to_bytes,
-    extract::{Path, Request, State},
-    http::HeaderValue,
+    extract::{Path, Query, Request, State},
+    http::{header::CONTENT_TYPE, HeaderValue},
     response::{AppendHeaders, IntoResponse},
     Json,
 };
@@ -16,19 +15,30 @@ use tasks_tracker_client::ResponseNewTask;
 use tokio::{
     spawn,
     sync::mpsc::{self, Receiver},
-    task::JoinHandle,
+    task::{spawn_blocking, JoinHandle},
 };
+use tracing::warn;
 
 use crate::{
-    cover::{all_id, all_id_missing_retrievable, retrieve_cover, update_table_image, CoverSize},
+    attempt::{attempts_of, prune_attempts},
+    cover::{
+        all_id, all_id_missing_retrievable, all_id_with_original, get_product, retrieval_status,
+        retrieve_cover, set_original, update_table_image, RetrieveOptions,
+    },
     error::AppError,
-    image::write_cover,
-    provider::CoverProvider,
+    image::{
+        delete_all_resized, delete_resized, original_path, regenerate_cover, webp_encoding,
+        write_cover, CoverSize,
+    },
+    provider::{all_candidates, circuits_status, CoverProvider, OutcomeKind},
     AppState,
 };
 
+/// retrieve the cover of the product.
+/// With force, the cover is retrieved even if it exists, manual covers need override_manual too.
 pub async fn retrieve_cover_handle(
     Path(id): Path<u32>,
+    Query(options): Query<RetrieveOptions>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
     // create a task and return the location for it.
@@ -36,7 +46,7 @@ pub async fn retrieve_cover_handle(
         .client_task
         .create_simple_task(
             String::from("cover api"),
-            format!("retrieve cover for product {}", id),
+            format!("retrieve cover for product {id}"),
             None,
         )
         .await
@@ -49,7 +59,7 @@ pub async fn retrieve_cover_handle(
     ]);
     // start the job, update task tracker and cache
     spawn(enc!((state, rep)async move {
-        wrapper_retrieve_cover(id, state, &rep).await.unwrap();
+        wrapper_retrieve_cover(id, state, &rep, options).await.unwrap();
     }));
 
     // the task began and token to review it is given back.
@@ -62,6 +72,7 @@ async fn wrapper_retrieve_cover(
     id: u32,
     state: AppState,
     rep: &ResponseNewTask,
+    options: RetrieveOptions,
 ) -> Result<(), AppError> {
     // channel to receive progress from task and send it to task tracker.
     let (update_progress, receive_progress) = mpsc::channel(4);
@@ -71,11 +82,13 @@ async fn wrapper_retrieve_cover(
             id,
             &state.conn_db_cover,
             &state.client_product,
-            &state.config.path_covers,
-            state.config.wait_seconds_retry_retrieve_cover,
+            &state.config,
+            options,
             update_progress,
         )
         .await
+        // the routing rule is shown when the task is finished.
+        .map(|route| route.map(|r| format!("providers of the routing rule {r} were asked")))
     }));
     let location = rep.location.to_owned();
     let token_update = rep.update_token.clone();
@@ -136,14 +149,13 @@ async fn wrapper_retrieve_missing_covers(
     // channel to receive progress from task and send it to task tracker.
     let (update_progress, receive_progress) = mpsc::channel(4);
     // start job
+    // the history of attempts is cleaned at each run.
+    let conn = state.conn_db_cover.get().await?;
+    prune_attempts(&conn, state.config.attempts_retention_days).await?;
+    drop(conn);
     // get list of id to retrieve
     // if this step fail, cover db or product api is misconfigured, abort task.
-    let missing_ids = get_missing_id(
-        &state.conn_db_cover,
-        &state.client_product,
-        state.config.wait_seconds_retry_retrieve_cover,
-    )
-    .await?;
+    let missing_ids = get_missing_id(&state.conn_db_cover, &state.client_product).await?;
     let mut count = 0;
     let ids = missing_ids
         .into_iter()
@@ -161,8 +173,8 @@ async fn wrapper_retrieve_missing_covers(
                 id,
                 &state.conn_db_cover,
                 &state.client_product,
-                &state.config.path_covers,
-                state.config.wait_seconds_retry_retrieve_cover,
+                &state.config,
+                RetrieveOptions::default(),
                 update_progress_unit,
             )
             .await
@@ -173,7 +185,7 @@ async fn wrapper_retrieve_missing_covers(
                 update_cache_cover(&state, id).await?;
             }
         }
-        Ok(())
+        Ok(None)
     }));
 
     let location = rep.location.to_owned();
@@ -191,10 +203,87 @@ async fn wrapper_retrieve_missing_covers(
     // cache is updated for each cover
 }
 
+/// Retrieve the covers of the products given.
+/// With force, covers are retrieved even if they exist, manual covers need override_manual too.
+pub async fn retrieve_covers(
+    Query(options): Query<RetrieveOptions>,
+    State(state): State<AppState>,
+    Json(ids): Json<Vec<u32>>,
+) -> Result<impl IntoResponse, AppError> {
+    // create a task and return the location for it.
+    let rep = state
+        .client_task
+        .create_simple_task(
+            String::from("cover api"),
+            format!("retrieve covers of {} products", ids.len()),
+            None,
+        )
+        .await
+        .map_err(|_| AppError::Backend)?;
+
+    // return headers from task tracker
+    let rep = Arc::new(rep);
+    let headers = AppendHeaders([
+        ("Content-Location", rep.location.as_str()),
+        ("ViewToken", &rep.view_token),
+    ]);
+    // start the job, update task tracker and cache
+    // errors will be for the task tracker
+    spawn(enc!((state, rep)async move {
+        wrapper_retrieve_covers(state, &rep, ids, options).await.unwrap();
+    }));
+
+    Ok((StatusCode::ACCEPTED, headers).into_response())
+}
+async fn wrapper_retrieve_covers(
+    state: AppState,
+    rep: &ResponseNewTask,
+    ids: Vec<u32>,
+    options: RetrieveOptions,
+) -> anyhow::Result<()> {
+    // channel to receive progress from task and send it to task tracker.
+    let (update_progress, receive_progress) = mpsc::channel(4);
+    let count = ids.len().max(1);
+    // for each id, retrieve cover, update cache
+    let handler = spawn(enc!((state) async move {
+        for (nb, id) in ids.into_iter().enumerate() {
+            let (update_progress_unit, _receive_progress_unit) = mpsc::channel(4);
+            // a product failing does not stop the others.
+            if retrieve_cover(
+                id,
+                &state.conn_db_cover,
+                &state.client_product,
+                &state.config,
+                options,
+                update_progress_unit,
+            )
+            .await
+            .is_ok()
+            {
+                update_cache_cover(&state, id).await?;
+            }
+            update_progress.send(((nb + 1) * 100 / count) as u8).await?;
+        }
+        Ok(None)
+    }));
+
+    let location = rep.location.to_owned();
+    let token_update = rep.update_token.clone();
+    // update task tracker
+    manage_tracker_status(
+        state.client_task,
+        &location,
+        token_update,
+        receive_progress,
+        handler,
+    )
+    .await?;
+    Ok(())
+}
+
 async fn get_missing_id(
     conn_db_cover: &Pool,
     client_product: &doli_client_api_rs::Client,
-    wait_try: u64,
 ) -> anyhow::Result<Vec<u32>> {
     // get all products id
     let mut products = client_product.get_all_products().await?;
@@ -203,7 +292,7 @@ async fn get_missing_id(
     let products_in_table_cover = all_id(&conn).await?;
     products.retain(|id| !products_in_table_cover.contains(id));
     // add ids that are present in table but missing covers that can be retrieved.
-    let id_covers_missing_retrievable = all_id_missing_retrievable(&conn, wait_try).await?;
+    let id_covers_missing_retrievable = all_id_missing_retrievable(&conn).await?;
     products.extend(id_covers_missing_retrievable);
     Ok(products)
 }
@@ -211,13 +300,7 @@ async fn get_missing_id(
 /// get the ids of every missing covers.
 /// should be protected behind admin authentication
 pub async fn get_missing_covers(State(state): State<AppState>) -> impl IntoResponse {
-    if let Ok(missing_ids) = get_missing_id(
-        &state.conn_db_cover,
-        &state.client_product,
-        state.config.wait_seconds_retry_retrieve_cover,
-    )
-    .await
-    {
+    if let Ok(missing_ids) = get_missing_id(&state.conn_db_cover, &state.client_product).await {
         return (StatusCode::OK, Json(missing_ids)).into_response();
     }
     StatusCode::INTERNAL_SERVER_ERROR.into_response()
@@ -268,11 +351,20 @@ async fn wrapper_add_manual_cover(
     let body = request.into_body();
     let handle_retrieve = spawn(enc!((state) async move {
         let bytes = to_bytes(body, usize::MAX).await?;
-        write_cover(&bytes, id, &state.config.path_covers)?;
+        let (path_covers, sizes) = (state.config.path_covers.clone(), state.config.sizes.clone());
+        let webp = webp_encoding(&state.config.formats);
+        let original =
+            spawn_blocking(move || write_cover(&bytes, id, &path_covers, &sizes, webp)).await??;
         update_progress.send(50).await?;
         let conn = state.conn_db_cover.get().await?;
-        update_table_image(id, &conn, Some(CoverProvider::Manual)).await?;
-        Ok(())
+        update_table_image(id, &conn, &state.config,
+            Some(CoverProvider::Manual),
+            Some(OutcomeKind::Found),
+            None,
+        )
+        .await?;
+        set_original(&conn, id, original).await?;
+        Ok(None)
     }));
     let location = rep.location.to_owned();
     let token_update = rep.update_token.clone();
@@ -294,25 +386,196 @@ async fn wrapper_add_manual_cover(
     Ok(())
 }
 
+/// ask every provider for the cover of the product without saving it.
+/// should be protected behind admin authentication
+pub async fn get_candidates(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let product = get_product(&state.client_product, &state.config, id)
+        .await
+        .map_err(|e| AppError::Backend.transmit_error(&e))?;
+    let conn = state.conn_db_cover.get().await?;
+    let candidates = all_candidates(&conn, &reqwest::Client::new(), &state.config, &product, id)
+        .await
+        .map_err(|e| AppError::Backend.transmit_error(&e))?;
+    let ttl = Duration::from_secs(state.config.candidates_ttl_seconds);
+    Ok(Json(state.candidates.insert(id, candidates, ttl)))
+}
+
+pub async fn preview_candidate(
+    Path((id, candidate)): Path<(u32, String)>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let cover = state
+        .candidates
+        .preview(id, &candidate)
+        .ok_or(AppError::CandidateNotFound)?;
+    // candidates were decoded when found, their format is known.
+    let content_type = image::guess_format(&cover)
+        .map_or("application/octet-stream", |format| format.to_mime_type());
+    Ok(([(CONTENT_TYPE, content_type)], cover))
+}
+
+/// Write again the covers of every product from their original, after the sizes were changed.
+/// should be protected behind admin authentication
+pub async fn regenerate_covers(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    // create a task and return the location for it.
+    let rep = state
+        .client_task
+        .create_simple_task(
+            String::from("cover api"),
+            String::from("regenerate covers from originals"),
+            None,
+        )
+        .await
+        .map_err(|_| AppError::Backend)?;
+
+    // return headers from task tracker
+    let rep = Arc::new(rep);
+    let headers = AppendHeaders([
+        ("Content-Location", rep.location.as_str()),
+        ("ViewToken", &rep.view_token),
+    ]);
+    // start the job, update task tracker and cache
+    // errors will be for the task tracker
+    spawn(enc!((state, rep)async move {
+        wrapper_regenerate_covers(state, &rep).await.unwrap();
+    }));
+
+    Ok((StatusCode::ACCEPTED, headers).into_response())
+}
+async fn wrapper_regenerate_covers(state: AppState, rep: &ResponseNewTask) -> anyhow::Result<()> {
+    // channel to receive progress from task and send it to task tracker.
+    let (update_progress, receive_progress) = mpsc::channel(4);
+    let handler = spawn(enc!((state) async move {
+        let conn = state.conn_db_cover.get().await?;
+        // renders kept of every cover were made with the previous sizes and formats.
+        let path_covers = state.config.path_covers.clone();
+        spawn_blocking(move || delete_all_resized(&path_covers)).await??;
+        let ids = all_id_with_original(&conn).await?;
+        let count = ids.len().max(1);
+        for (nb, id) in ids.into_iter().enumerate() {
+            let path_covers = state.config.path_covers.clone();
+            let sizes = state.config.sizes.clone();
+            let webp = webp_encoding(&state.config.formats);
+            // a product failing does not stop the others.
+            match spawn_blocking(move || regenerate_cover(id, &path_covers, &sizes, webp)).await? {
+                Ok(_) => update_cache_cover(&state, id).await?,
+                Err(e) => warn!("covers of product {id} could not be regenerated: {e}"),
+            }
+            update_progress.send(((nb + 1) * 100 / count) as u8).await?;
+        }
+        Ok(None)
+    }));
+
+    let location = rep.location.to_owned();
+    let token_update = rep.update_token.clone();
+    // update task tracker
+    manage_tracker_status(
+        state.client_task,
+        &location,
+        token_update,
+        receive_progress,
+        handler,
+    )
+    .await?;
+    Ok(())
+}
+
+/// save the candidate chosen as the cover of the product.
+pub async fn choose_candidate(
+    Path((id, candidate)): Path<(u32, String)>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let (provider, cover) = state
+        .candidates
+        .take(id, &candidate)
+        .ok_or(AppError::CandidateNotFound)?;
+    let (path_covers, sizes) = (state.config.path_covers.clone(), state.config.sizes.clone());
+    let webp = webp_encoding(&state.config.formats);
+    // decoding and resizing block the thread, the runtime must keep serving requests.
+    let original = spawn_blocking(move || write_cover(&cover, id, &path_covers, &sizes, webp))
+        .await
+        .map_err(|e| AppError::Backend.transmit_error(&e))?
+        .map_err(|e| AppError::Backend.transmit_error(&e))?;
+    let conn = state.conn_db_cover.get().await?;
+    update_table_image(
+        id,
+        &conn,
+        &state.config,
+        Some(provider),
+        Some(OutcomeKind::Found),
+        None,
+    )
+    .await
+    .map_err(|e| AppError::Backend.transmit_error(&e))?;
+    set_original(&conn, id, original)
+        .await
+        .map_err(|e| AppError::Backend.transmit_error(&e))?;
+    update_cache_cover(&state, id).await?;
+    Ok(())
+}
+
+/// explain what a retrieval of the cover of the product would do.
+/// should be protected behind admin authentication
+pub async fn get_retrieval_status(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = state.conn_db_cover.get().await?;
+    let status = retrieval_status(&conn, &state.client_product, &state.config, id)
+        .await
+        .map_err(|e| AppError::Backend.transmit_error(&e))?;
+    Ok(Json(status))
+}
+
+/// requests made to providers for the product, the most recent first.
+/// should be protected behind admin authentication
+pub async fn get_attempts(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let conn = state.conn_db_cover.get().await?;
+    let attempts = attempts_of(&conn, id)
+        .await
+        .map_err(|e| AppError::Backend.transmit_error(&e))?;
+    Ok(Json(attempts))
+}
+
+/// state of the circuit breaker of every provider.
+/// should be protected behind admin authentication
+pub async fn get_providers_status(State(state): State<AppState>) -> impl IntoResponse {
+    Json(circuits_status(&state.config.providers))
+}
+
 pub async fn delete_cover(
     Path(id): Path<u32>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
     // delete cover
-    delete_cover_files(id, state.config.path_covers.to_owned())?;
+    delete_cover_files(id, &state.config.path_covers, &state.config.sizes)?;
+    delete_resized(id, &state.config.path_covers)
+        .map_err(|e| AppError::Backend.transmit_error(&e))?;
     let conn = state.conn_db_cover.get().await?;
-    update_table_image(id, &conn, None).await?;
+    update_table_image(id, &conn, &state.config, None, None, None).await?;
     update_cache_cover(&state, id).await?;
     Ok(())
 }
 
-fn delete_cover_files(id: u32, mut path: PathBuf) -> Result<(), std::io::Error> {
-    path.push(id.to_string());
-    path.push("-");
-    for s in CoverSize::iter() {
-        let mut path = path.to_path_buf();
-        path.push(s.to_string());
-        remove_file(path)?;
+fn delete_cover_files(id: u32, path: &FsPath, sizes: &[CoverSize]) -> Result<(), std::io::Error> {
+    let files = sizes
+        .iter()
+        .map(|size| path.join(size.file_name(id)))
+        .chain([original_path(path, id)]);
+    for file in files {
+        match remove_file(file) {
+            // a size added after the cover was written does not have a file, nor a cover written before originals were kept.
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            result => result?,
+        }
     }
     Ok(())
 }
@@ -321,8 +584,15 @@ async fn update_cache_cover(state: &AppState, id: u32) -> Result<(), AppError> {
     // path API cache
     let uri = state.config.cache_api_uri.clone();
     // path cover API
-    for variant in CoverSize::iter() {
-        let url = format!("{uri}/api/1/cache/path/{id}/cover-{variant}");
+    // covers resized on demand share the path /{id}/cover.
+    let paths = state
+        .config
+        .sizes
+        .iter()
+        .map(|size| format!("cover-{}", size.name))
+        .chain([String::from("cover")]);
+    for path in paths {
+        let url = format!("{uri}/api/1/cache/path/{id}/{path}");
         let host = &state.config.hostname;
         state
             .client_cache
@@ -342,7 +612,7 @@ async fn manage_tracker_status(
     task_location: &Url,
     token_update: String,
     mut receiver: Receiver<u8>,
-    handler: JoinHandle<anyhow::Result<()>>,
+    handler: JoinHandle<anyhow::Result<Option<String>>>,
 ) -> anyhow::Result<()> {
     // check for updates in a loop and update task tracker
     // job should finish when receiver is done
@@ -360,20 +630,23 @@ async fn manage_tracker_status(
     }));
     // listen for incoming abort and abort job
     // check if the job is finished. If error, put status aborted on task tracker with error description.
-    // if ok, status finish
-    if let Err(err) = handler.await {
-        client
-            .abort_task(
-                task_location,
-                Some(&err.to_string()),
-                &[],
-                Some(&token_update),
-            )
-            .await?;
-        bail!("task had an issue and was aborted");
-    }
+    // if ok, status finish, with the message the job may give.
+    let message = match handler.await {
+        Err(err) => {
+            client
+                .abort_task(
+                    task_location,
+                    Some(&err.to_string()),
+                    &[],
+                    Some(&token_update),
+                )
+                .await?;
+            bail!("task had an issue and was aborted");
+        }
+        Ok(result) => result.ok().flatten(),
+    };
     client
-        .finish_task(task_location, None, &[], Some(&token_update))
+        .finish_task(task_location, message.as_deref(), &[], Some(&token_update))
         .await?;
     Ok(())
 }
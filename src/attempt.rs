@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDateTime, Utc};
+use deadpool_diesel::mysql::Object;
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::db::Attempt;
+use crate::provider::OutcomeKind;
+use crate::schema::cover_attempts::dsl::{cover_attempts, product_id, tried_at};
+
+/// attempt as shown to the staff.
+#[derive(Serialize)]
+pub struct AttemptView {
+    pub provider: String,
+    pub barcode: String,
+    pub tried_at: NaiveDateTime,
+    pub http_status: Option<u16>,
+    pub outcome: Option<OutcomeKind>,
+    pub error: Option<String>,
+    pub duration_ms: u32,
+}
+
+/// keep the result of a request made to a provider.
+#[allow(clippy::too_many_arguments)]
+pub async fn record_attempt(
+    conn: &Object,
+    id: u32,
+    provider: String,
+    barcode: &str,
+    http_status: Option<u16>,
+    outcome: OutcomeKind,
+    error: Option<String>,
+    duration: Duration,
+) -> Result<()> {
+    let attempt = Attempt {
+        id: 0,
+        product_id: id,
+        provider,
+        barcode: barcode.to_string(),
+        tried_at: Utc::now().naive_utc(),
+        http_status,
+        outcome: outcome.code(),
+        error,
+        duration_ms: duration.as_millis().try_into().unwrap_or(u32::MAX),
+    };
+    conn.interact(move |conn| {
+        diesel::insert_into(cover_attempts)
+            .values(&attempt)
+            .execute(conn)
+    })
+    .await
+    .map_err(|e| anyhow!(e.to_string()))??;
+    Ok(())
+}
+
+/// every attempt made for the product, the most recent first.
+pub async fn attempts_of(conn: &Object, id: u32) -> Result<Vec<AttemptView>> {
+    let attempts: Vec<Attempt> = conn
+        .interact(move |conn| {
+            cover_attempts
+                .filter(product_id.eq(id))
+                .order(tried_at.desc())
+                .select(Attempt::as_select())
+                .load(conn)
+        })
+        .await
+        .map_err(|e| anyhow!(e.to_string()))??;
+    Ok(attempts
+        .into_iter()
+        .map(|a| AttemptView {
+            provider: a.provider,
+            barcode: a.barcode,
+            tried_at: a.tried_at,
+            http_status: a.http_status,
+            outcome: OutcomeKind::from_code(a.outcome),
+            error: a.error,
+            duration_ms: a.duration_ms,
+        })
+        .collect())
+}
+
+/// delete the attempts older than the retention.
+pub async fn prune_attempts(conn: &Object, retention_days: u32) -> Result<usize> {
+    let limit = Utc::now().naive_utc() - chrono::Duration::days(retention_days.into());
+    Ok(conn
+        .interact(move |conn| {
+            diesel::delete(cover_attempts.filter(tried_at.lt(limit))).execute(conn)
+        })
+        .await
+        .map_err(|e| anyhow!(e.to_string()))??)
+}
@@ -0,0 +1,233 @@
+use derive_more::Display;
+use thiserror::Error;
+
+/// kind of barcode, found from its length, prefix and checksum.
+#[derive(Clone, Copy, Display, PartialEq, Debug)]
+pub enum BarcodeKind {
+    #[display(fmt = "EAN-13")]
+    Ean13,
+    #[display(fmt = "EAN-8")]
+    Ean8,
+    #[display(fmt = "UPC-A")]
+    UpcA,
+    #[display(fmt = "ISBN-10")]
+    Isbn10,
+    #[display(fmt = "ISBN-13")]
+    Isbn13,
+    #[display(fmt = "ISMN")]
+    Ismn,
+    #[display(fmt = "ISSN")]
+    Issn,
+}
+
+/// reason for a barcode to be refused.
+#[derive(Error, Debug)]
+pub enum BarcodeError {
+    #[error("barcode is empty")]
+    Empty,
+    #[error("barcode contains the invalid character '{0}'")]
+    Character(char),
+    #[error("barcode of {0} characters does not match any known format")]
+    Length(usize),
+    #[error("checksum of the {0} barcode is invalid")]
+    Checksum(BarcodeKind),
+}
+
+/// barcode validated and normalized, without separators.
+#[derive(Clone, Debug)]
+pub struct Barcode {
+    kind: BarcodeKind,
+    code: String,
+}
+
+impl Barcode {
+    /// normalize a barcode as stored in the product API and validate its checksum.
+    pub fn parse(raw: &str) -> Result<Self, BarcodeError> {
+        let raw = raw.trim();
+        // ISSN are usually written 1234-5679, which can not be distinguished from an EAN-8 otherwise.
+        let issn_format = raw.len() == 9 && raw.as_bytes()[4] == b'-';
+        let code: String = raw
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if code.is_empty() {
+            return Err(BarcodeError::Empty);
+        }
+        // ISMN of the old format, M followed by 9 digits.
+        if let Some(digits) = code.strip_prefix('M') {
+            let ean = ["9790", digits].concat();
+            return match Self::parse(&ean)? {
+                barcode if barcode.kind == BarcodeKind::Ismn => Ok(barcode),
+                _ => Err(BarcodeError::Character('M')),
+            };
+        }
+        if let Some(c) = code.chars().find(|c| !c.is_ascii_digit() && *c != 'X') {
+            return Err(BarcodeError::Character(c));
+        }
+        // only the check digit can be X
+        let (payload, check) = code.split_at(code.len() - 1);
+        if payload.contains('X') {
+            return Err(BarcodeError::Character('X'));
+        }
+        let check = check.chars().next().unwrap_or_default();
+        let kind = match code.len() {
+            13 => {
+                let kind = match &code[..3] {
+                    "979" if code.starts_with("9790") => BarcodeKind::Ismn,
+                    "978" | "979" => BarcodeKind::Isbn13,
+                    "977" => BarcodeKind::Issn,
+                    _ => BarcodeKind::Ean13,
+                };
+                valid(gtin_check(payload), check, kind)?
+            }
+            12 => valid(gtin_check(payload), check, BarcodeKind::UpcA)?,
+            10 => valid(mod11_check(payload), check, BarcodeKind::Isbn10)?,
+            8 if issn_format || gtin_check(payload) != check => {
+                valid(mod11_check(payload), check, BarcodeKind::Issn)?
+            }
+            8 => BarcodeKind::Ean8,
+            n => return Err(BarcodeError::Length(n)),
+        };
+        Ok(Self { kind, code })
+    }
+    pub fn kind(&self) -> BarcodeKind {
+        self.kind
+    }
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+    /// ISBN-13 of a book.
+    pub fn isbn13(&self) -> Option<String> {
+        match self.kind {
+            BarcodeKind::Isbn13 => Some(self.code.clone()),
+            BarcodeKind::Isbn10 => Some(with_check(&["978", &self.code[..9]].concat(), gtin_check)),
+            _ => None,
+        }
+    }
+    /// ISBN-10 of a book, only existing for ISBN-13 beginning with 978.
+    pub fn isbn10(&self) -> Option<String> {
+        match self.kind {
+            BarcodeKind::Isbn10 => Some(self.code.clone()),
+            BarcodeKind::Isbn13 if self.code.starts_with("978") => {
+                Some(with_check(&self.code[3..12], mod11_check))
+            }
+            _ => None,
+        }
+    }
+    /// every equivalent forms of the barcode, the normalized code first.
+    pub fn forms(&self) -> Vec<String> {
+        let code = &self.code;
+        let other = match self.kind {
+            BarcodeKind::Isbn10 => self.isbn13(),
+            BarcodeKind::Isbn13 => self.isbn10(),
+            BarcodeKind::UpcA => Some(["0", code].concat()),
+            BarcodeKind::Ean13 => code.strip_prefix('0').map(str::to_string),
+            BarcodeKind::Ismn => Some(["M", &code[4..]].concat()),
+            BarcodeKind::Issn if code.len() == 8 => {
+                Some(with_check(&["977", &code[..7], "00"].concat(), gtin_check))
+            }
+            BarcodeKind::Issn => Some(with_check(&code[3..10], mod11_check)),
+            BarcodeKind::Ean8 => None,
+        };
+        [Some(code.clone()), other].into_iter().flatten().collect()
+    }
+}
+
+fn valid(expected: char, check: char, kind: BarcodeKind) -> Result<BarcodeKind, BarcodeError> {
+    if check == expected {
+        Ok(kind)
+    } else {
+        Err(BarcodeError::Checksum(kind))
+    }
+}
+
+fn with_check(payload: &str, check: fn(&str) -> char) -> String {
+    [payload, &check(payload).to_string()].concat()
+}
+
+fn digits(payload: &str) -> impl DoubleEndedIterator<Item = u32> + '_ {
+    payload.chars().filter_map(|c| c.to_digit(10))
+}
+
+/// check digit of EAN-13, EAN-8, UPC-A: weights 3 and 1 from the right.
+fn gtin_check(payload: &str) -> char {
+    let sum: u32 = digits(payload)
+        .rev()
+        .zip([3, 1].into_iter().cycle())
+        .map(|(d, w)| d * w)
+        .sum();
+    char::from_digit((10 - sum % 10) % 10, 10).unwrap_or('0')
+}
+
+/// check digit modulo 11 of ISBN-10 and ISSN: weights 2 to n from the right.
+fn mod11_check(payload: &str) -> char {
+    let sum: u32 = digits(payload).rev().zip(2..).map(|(d, w)| d * w).sum();
+    match (11 - sum % 11) % 11 {
+        10 => 'X',
+        c => char::from_digit(c, 10).unwrap_or('0'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(raw: &str) -> BarcodeKind {
+        Barcode::parse(raw).expect("barcode should be valid").kind()
+    }
+
+    #[test]
+    fn kinds_from_length_and_prefix() {
+        assert_eq!(kind("0-306-40615-2"), BarcodeKind::Isbn10);
+        assert_eq!(kind("080442957x"), BarcodeKind::Isbn10);
+        assert_eq!(kind("978-0-306-40615-7"), BarcodeKind::Isbn13);
+        assert_eq!(kind("9790230671187"), BarcodeKind::Ismn);
+        assert_eq!(kind("M-2306-7118-7"), BarcodeKind::Ismn);
+        assert_eq!(kind("9770317847001"), BarcodeKind::Issn);
+        assert_eq!(kind("0317-8471"), BarcodeKind::Issn);
+        assert_eq!(kind("036000291452"), BarcodeKind::UpcA);
+        assert_eq!(kind("96385074"), BarcodeKind::Ean8);
+        assert_eq!(kind("4006381333931"), BarcodeKind::Ean13);
+    }
+
+    #[test]
+    fn invalid_barcodes() {
+        assert!(matches!(Barcode::parse(" "), Err(BarcodeError::Empty)));
+        assert!(matches!(
+            Barcode::parse("97803064A6157"),
+            Err(BarcodeError::Character('A'))
+        ));
+        assert!(matches!(
+            Barcode::parse("0X06406152"),
+            Err(BarcodeError::Character('X'))
+        ));
+        assert!(matches!(
+            Barcode::parse("12345"),
+            Err(BarcodeError::Length(5))
+        ));
+        assert!(matches!(
+            Barcode::parse("9780306406158"),
+            Err(BarcodeError::Checksum(BarcodeKind::Isbn13))
+        ));
+        assert!(matches!(
+            Barcode::parse("0306406153"),
+            Err(BarcodeError::Checksum(BarcodeKind::Isbn10))
+        ));
+    }
+
+    #[test]
+    fn forms_of_the_barcode() {
+        let forms = |raw: &str| Barcode::parse(raw).unwrap().forms();
+        assert_eq!(forms("0-306-40615-2"), ["0306406152", "9780306406157"]);
+        assert_eq!(forms("9780306406157"), ["9780306406157", "0306406152"]);
+        // ISBN-13 beginning with 979 have no ISBN-10.
+        assert_eq!(forms("9791032305690"), ["9791032305690"]);
+        assert_eq!(forms("036000291452"), ["036000291452", "0036000291452"]);
+        assert_eq!(forms("0036000291452"), ["0036000291452", "036000291452"]);
+        assert_eq!(forms("9790230671187"), ["9790230671187", "M230671187"]);
+        assert_eq!(forms("0317-8471"), ["03178471", "9770317847001"]);
+        assert_eq!(forms("9770317847001"), ["9770317847001", "03178471"]);
+        assert_eq!(forms("96385074"), ["96385074"]);
+    }
+}
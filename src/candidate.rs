@@ -0,0 +1,85 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::provider::{Candidate, CoverProvider};
+
+// candidate and its expiration, by product id and candidate id.
+type Candidates = HashMap<(u32, String), (Instant, Candidate)>;
+
+/// candidates kept in memory until the staff choose one or they expire.
+#[derive(Clone, Default)]
+pub struct CandidateStore {
+    candidates: Arc<Mutex<Candidates>>,
+}
+
+/// candidate as listed to the staff.
+#[derive(Serialize)]
+pub struct CandidateView {
+    pub id: String,
+    pub provider: String,
+    pub width: u32,
+    pub height: u32,
+    pub source: String,
+    // url of the image, relative to this API.
+    pub preview: String,
+    pub expires_in: u64,
+}
+
+impl CandidateStore {
+    /// replace the candidates of the product and return how they can be previewed.
+    pub fn insert(
+        &self,
+        product_id: u32,
+        candidates: Vec<Candidate>,
+        ttl: Duration,
+    ) -> Vec<CandidateView> {
+        let mut store = self.candidates.lock().expect("candidate store poisoned");
+        let now = Instant::now();
+        store.retain(|(id, _), (expire, _)| *id != product_id && *expire > now);
+        candidates
+            .into_iter()
+            .map(|candidate| {
+                let id = candidate_id(&candidate.provider, &candidate.cover);
+                let view = CandidateView {
+                    preview: format!("/{product_id}/candidates/{id}/preview"),
+                    id: id.clone(),
+                    provider: candidate.provider.to_string(),
+                    width: candidate.info.width,
+                    height: candidate.info.height,
+                    source: candidate.source.clone(),
+                    expires_in: ttl.as_secs(),
+                };
+                store.insert((product_id, id), (now + ttl, candidate));
+                view
+            })
+            .collect()
+    }
+    /// image of a candidate not expired.
+    pub fn preview(&self, product_id: u32, id: &str) -> Option<Vec<u8>> {
+        let store = self.candidates.lock().expect("candidate store poisoned");
+        store
+            .get(&(product_id, id.to_string()))
+            .filter(|(expire, _)| *expire > Instant::now())
+            .map(|(_, c)| c.cover.clone())
+    }
+    /// remove the candidate chosen and every other candidate of the product.
+    pub fn take(&self, product_id: u32, id: &str) -> Option<(CoverProvider, Vec<u8>)> {
+        let mut store = self.candidates.lock().expect("candidate store poisoned");
+        let (expire, candidate) = store.remove(&(product_id, id.to_string()))?;
+        store.retain(|(pid, _), _| *pid != product_id);
+        (expire > Instant::now()).then_some((candidate.provider, candidate.cover))
+    }
+}
+
+/// the same image sent by two providers has two ids, the one chosen is credited with the cover.
+fn candidate_id(provider: &CoverProvider, cover: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    provider.to_string().hash(&mut hasher);
+    cover.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
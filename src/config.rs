@@ -1,14 +1,18 @@
+use anyhow::{bail, Result};
 use reqwest::Url;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::path::PathBuf;
 
-use crate::provider::CoverProvider;
+use crate::image::{CoverSize, Dimension, Encoding};
+use crate::placeholder::PlaceholderConfig;
+use crate::provider::{CircuitBreaker, CoverProvider, RateLimit};
 
 const MSG_PANIC_DEFAULT_CONFIG: &str =
     "default config should not panic while parsing values of Url";
 
-// configuration struct
+// configuration struct, settings missing from the file take their default value.
 #[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct Config {
     // cover database connection
     pub cover_db_uri: Url,
@@ -17,8 +21,25 @@ pub struct Config {
     pub listen_port: u16,
     // path where the cover files will be stored.
     pub path_covers: PathBuf,
-    // time to wait before retrying getting the cover in seconds.
+    // sizes in which every cover is written.
+    pub sizes: Vec<CoverSize>,
+    // dimensions in which covers can be resized on demand, served as /{id}/cover?w={width}&h={height}.
+    // Every resized cover is kept on disk, so only these dimensions are allowed.
+    pub resize_dimensions: Vec<Dimension>,
+    // formats in which covers can be served, chosen by the format parameter or the Accept header of the request.
+    // The first one is served when the client has no preference. Covers are stored in WebP with its settings.
+    pub formats: Vec<Encoding>,
+    // time to wait before retrying getting the cover in seconds, when providers did not have it.
+    // The time is multiplied by retry_backoff_factor after each retrieval without cover, up to wait_seconds_retry_max.
     pub wait_seconds_retry_retrieve_cover: u64,
+    pub retry_backoff_factor: f64,
+    pub wait_seconds_retry_max: u64,
+    // time to wait in seconds before retrying after a provider failed to answer.
+    pub wait_seconds_retry_transient: u64,
+    // time to wait in seconds before retrying after a provider refused the request, for example with an invalid API key.
+    pub wait_seconds_retry_permanent: u64,
+    // days during which the requests made to providers are kept in the cover DB.
+    pub attempts_retention_days: u32,
     // product API connection, can be the same as the cover database.
     pub product_api_uri: Url,
     pub product_api_path_pass: PathBuf,
@@ -28,7 +49,24 @@ pub struct Config {
     // to return a uri of a task to track instead of waiting the operation.
     pub tasks_api_uri: Url,
     pub tasks_api_pass_path: PathBuf,
-    pub providers: Vec<CoverProvider>,
+    // providers used to retrieve covers, in order of priority.
+    // A provider can be written by its name only, with the settings by default, as in the first versions.
+    #[serde(deserialize_with = "providers_or_names")]
+    pub providers: Vec<ProviderConfig>,
+    // rules choosing the providers for some products, checked in order. Products matching no rule use every provider.
+    pub routes: Vec<Route>,
+    // keep the cover of the first provider having one, or ask every provider and keep the best cover.
+    pub strategy: Strategy,
+    // providers failing too many times in a row are skipped for some time.
+    pub circuit_breaker: CircuitBreaker,
+    // height/width ratio expected for covers, used to choose the best cover.
+    pub cover_ratio: f32,
+    // time in seconds during which candidates of a product can be previewed and chosen.
+    pub candidates_ttl_seconds: u64,
+    // search by title and author for products without barcode.
+    pub fuzzy_search: FuzzySearch,
+    // images sent by providers refused as covers.
+    pub placeholder: PlaceholderConfig,
     // domain name used for this instance of cover API. Used for cache API
     pub hostname: String,
 }
@@ -41,7 +79,15 @@ impl Default for Config {
             cover_db_path_pass: PathBuf::from("admin/db/cover"),
             listen_port: 8000,
             path_covers: PathBuf::new(),
+            sizes: CoverSize::defaults(),
+            resize_dimensions: Dimension::defaults(),
+            formats: Encoding::defaults(),
             wait_seconds_retry_retrieve_cover: 3600,
+            retry_backoff_factor: 2.0,
+            wait_seconds_retry_max: 2592000,
+            wait_seconds_retry_transient: 300,
+            wait_seconds_retry_permanent: 86400,
+            attempts_retention_days: 30,
             product_api_uri: Url::parse("https://dolibarr.example.net")
                 .expect(MSG_PANIC_DEFAULT_CONFIG),
             product_api_path_pass: PathBuf::from("admin/dolibarr/api_key"),
@@ -51,8 +97,199 @@ impl Default for Config {
             tasks_api_uri: Url::parse("https://cover_worker@tasks.example.net")
                 .expect(MSG_PANIC_DEFAULT_CONFIG),
             tasks_api_pass_path: PathBuf::from("admin/tasks-tracker/token"),
-            providers: vec![CoverProvider::OpenLibrary],
+            providers: vec![ProviderConfig::new(CoverProvider::OpenLibrary)],
+            routes: Vec::new(),
+            strategy: Strategy::First,
+            circuit_breaker: CircuitBreaker::default(),
+            cover_ratio: 1.5,
+            candidates_ttl_seconds: 600,
+            fuzzy_search: FuzzySearch::default(),
+            placeholder: PlaceholderConfig::default(),
             hostname: "covers.example.net".to_string(),
         }
     }
 }
+
+// how the cover is chosen among providers
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub enum Strategy {
+    // providers are asked in order of priority, the first cover found is kept.
+    First,
+    // every provider is asked at the same time, the cover with the best resolution and aspect ratio is kept.
+    Best,
+}
+
+// providers used for the products matching every condition set.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Route {
+    // name of the rule shown in the logs and the task tracker.
+    pub name: String,
+    // beginning of the barcode, in any of its forms, for example 978 and 979 for books.
+    #[serde(default)]
+    pub barcode_prefixes: Vec<String>,
+    // ids of the categories of the product API.
+    #[serde(default)]
+    pub categories: Vec<u32>,
+    // types of product of the product API.
+    #[serde(default)]
+    pub product_types: Vec<u8>,
+    // names of the providers as shown in the logs, in order of priority.
+    pub providers: Vec<String>,
+}
+
+// search of the cover on OpenLibrary by title and author, for products without barcode.
+// Covers found this way are marked in the cover DB to be reviewed.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct FuzzySearch {
+    pub enabled: bool,
+    // url of the OpenLibrary search API.
+    pub search_url: Url,
+    // name of the extrafield of the product API containing the author.
+    pub author_extrafield: Option<String>,
+    // similarity between 0 and 1 required between the product and the book found.
+    pub min_similarity: f64,
+}
+
+impl Default for FuzzySearch {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            search_url: Url::parse("https://openlibrary.org").expect(MSG_PANIC_DEFAULT_CONFIG),
+            author_extrafield: None,
+            min_similarity: 0.9,
+        }
+    }
+}
+
+impl FuzzySearch {
+    /// verify the settings can be used by this build.
+    pub fn check(&self) -> Result<()> {
+        if self.enabled && !cfg!(feature = "openlibrary") {
+            bail!("fuzzy search uses OpenLibrary but covers-api was compiled without the feature \"openlibrary\"");
+        }
+        if !(0.0..=1.0).contains(&self.min_similarity) {
+            bail!("min_similarity of fuzzy search must be between 0 and 1");
+        }
+        Ok(())
+    }
+}
+
+// settings of a cover provider
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProviderConfig {
+    pub provider: CoverProvider,
+    // a disabled provider is skipped but keep his settings.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // time to wait for a response of the provider in seconds.
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    // replace the default url of the provider API.
+    pub base_url: Option<Url>,
+    // path of the pass file containing the API key, for providers requiring one.
+    pub api_key_path_pass: Option<PathBuf>,
+    // number of requests made to the provider for a product before trying the next provider.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u8,
+    // number of calls allowed per day for providers with paid quotas, counted in the cover DB.
+    pub daily_quota: Option<u32>,
+    // requests allowed to the provider, without limit if not set.
+    pub rate_limit: Option<RateLimit>,
+    // User-Agent sent to providers requiring one, with the name of the application and a contact.
+    pub user_agent: Option<String>,
+    // ids of the categories of the product API for which the provider is used, every product if empty.
+    #[serde(default)]
+    pub categories: Vec<u32>,
+    // API key read from api_key_path_pass at startup.
+    #[serde(skip)]
+    pub api_key: Option<String>,
+}
+
+impl ProviderConfig {
+    /// settings by default for a provider
+    pub fn new(provider: CoverProvider) -> Self {
+        Self {
+            provider,
+            enabled: default_enabled(),
+            timeout_seconds: default_timeout_seconds(),
+            base_url: None,
+            api_key_path_pass: None,
+            max_attempts: default_max_attempts(),
+            daily_quota: None,
+            rate_limit: None,
+            user_agent: None,
+            categories: Vec::new(),
+            api_key: None,
+        }
+    }
+}
+
+// provider of the configuration file, with its settings or by its name.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProviderEntry {
+    Name(CoverProvider),
+    Settings(Box<ProviderConfig>),
+}
+
+fn providers_or_names<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<ProviderConfig>, D::Error> {
+    let entries = Vec::<ProviderEntry>::deserialize(deserializer)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| match entry {
+            ProviderEntry::Name(provider) => ProviderConfig::new(provider),
+            ProviderEntry::Settings(settings) => *settings,
+        })
+        .collect())
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_max_attempts() -> u8 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn providers_written_by_name() {
+        let config: Config = toml::from_str(r#"providers = ["OpenLibrary"]"#).unwrap();
+        let [settings] = config.providers.as_slice() else {
+            panic!("one provider expected");
+        };
+        assert!(settings.provider == CoverProvider::OpenLibrary);
+        assert!(settings.enabled);
+        assert_eq!(settings.timeout_seconds, 10);
+        assert_eq!(settings.max_attempts, 1);
+        // the other settings keep their default value.
+        assert_eq!(config.listen_port, Config::default().listen_port);
+    }
+
+    #[test]
+    fn providers_by_name_and_with_settings() {
+        let config: Config = toml::from_str(
+            r#"
+            providers = [
+                "OpenLibrary",
+                { provider = "GoogleBooks", timeout_seconds = 5, enabled = false },
+            ]
+            "#,
+        )
+        .unwrap();
+        let settings = &config.providers[1];
+        assert!(settings.provider == CoverProvider::GoogleBooks);
+        assert!(!settings.enabled);
+        assert_eq!(settings.timeout_seconds, 5);
+        assert_eq!(settings.max_attempts, 1);
+    }
+}
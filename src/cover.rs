@@ -1,32 +1,38 @@
-use crate::schema::covers::{dsl::covers, id, provider};
-use std::path::Path;
+use crate::schema::covers::{dsl::covers, id, next_try_at, provider};
 use std::time::Duration;
 
+use crate::barcode::{Barcode, BarcodeError};
+use crate::config::Config;
 use crate::db::Cover;
 use crate::error::AppError;
-use crate::provider::{try_get_cover, CoverProvider};
+use crate::image::Original;
+use crate::provider::{
+    route_for, try_get_cover, try_get_cover_by_title, CoverProvider, OutcomeKind, Product,
+};
 use crate::schema::{self};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use chrono::{NaiveDateTime, Utc};
 use deadpool_diesel::mysql::{Object, Pool};
-use derive_more::Display;
 use diesel::{
     dsl::{exists, select},
     prelude::*,
 };
 use reqwest::Client;
-/// size of covers
-use strum_macros::EnumIter;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tokio::sync::mpsc::Sender;
-#[derive(Display, EnumIter)]
-pub enum CoverSize {
-    #[display = "L"]
-    Large,
-    #[display = "M"]
-    Medium,
-    #[display = "S"]
-    Small,
+use tracing::debug;
+/// how retrieve_cover treats a product having a cover or waiting for its next retrieval.
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct RetrieveOptions {
+    // retrieve the cover even if it exists or the wait time did not expire, replacing the existing one.
+    #[serde(default)]
+    pub force: bool,
+    // with force, replace a cover uploaded manually too.
+    #[serde(default)]
+    pub override_manual: bool,
 }
+
 /// retrieve a cover for a product if conditions are met.
 /// product must exist on product API,
 /// product do not already have a cover
@@ -34,45 +40,266 @@ pub enum CoverSize {
 /// Send update on the progress
 /// Use the Product API given
 /// Interact with write access to the Cover DB.
-/// Right now, retrieve cover can retrieve only cover by barcode.
+/// Products without barcode are searched by title and author if fuzzy search is enabled.
+/// The name of the routing rule used is returned, there is none for a search by title.
 pub async fn retrieve_cover(
     product_id: u32,
     pool: &Pool,
     client: &doli_client_api_rs::Client,
-    path_cover: &Path,
-    wait_retry: u64,
+    config: &Config,
+    options: RetrieveOptions,
     // using channel to be task tracker agnostic.
     sender_task_progress: Sender<u8>,
-) -> Result<()> {
+) -> Result<Option<String>> {
     let conn = pool.get().await?;
-    get_status_must_get_image(&conn, product_id, wait_retry).await?;
-    let barcode = get_barcode(client, product_id).await?;
+    if options.force {
+        check_manual_protected(&conn, product_id, options).await?;
+    } else {
+        get_status_must_get_image(&conn, product_id).await?;
+    }
+    let product = match get_product(client, config, product_id).await {
+        Ok(p) => p,
+        // an invalid barcode will not be found by providers.
+        Err(e) if e.is::<BarcodeError>() => {
+            let failure = format!("{e:#}");
+            update_table_image(
+                product_id,
+                &conn,
+                config,
+                None,
+                Some(OutcomeKind::PermanentError),
+                Some(failure.clone()),
+            )
+            .await?;
+            bail!(failure);
+        }
+        // fallback on the title and author of the product.
+        Err(e) if e.is::<NoBarcode>() && config.fuzzy_search.enabled => {
+            let title = client.get_label_from_id(product_id).await?;
+            let author = match &config.fuzzy_search.author_extrafield {
+                Some(field) => client.get_extrafield_from_id(product_id, field).await?,
+                None => None,
+            };
+            sender_task_progress.send(50).await?;
+            try_get_cover_by_title(
+                &conn,
+                &Client::new(),
+                config,
+                &title,
+                author.as_deref(),
+                product_id,
+            )
+            .await?;
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
     // progress update, conditions to get cover are met
     sender_task_progress.send(50).await?;
     // get it
     let client_provider = Client::new();
-    let providers = vec![
-        #[cfg(feature = "openlibrary")]
-        CoverProvider::OpenLibrary,
-    ];
-    try_get_cover(
-        &conn,
-        &client_provider,
-        path_cover,
-        providers,
-        &barcode,
-        product_id,
-    )
-    .await?;
-    Ok(())
+    let route = try_get_cover(&conn, &client_provider, config, &product, product_id).await?;
+    Ok(Some(route))
+}
+/// what retrieve_cover would do for the product right now.
+#[derive(Serialize)]
+pub enum Decision {
+    Retrieve,
+    CoverExists,
+    Waiting,
+    ProductNotFound,
+    NoBarcode,
+    InvalidBarcode,
+    FuzzySearch,
+}
+
+/// everything known about the retrieval of the cover of a product.
+#[derive(Serialize)]
+pub struct RetrievalStatus {
+    pub product_exists: bool,
+    pub barcode: Option<String>,
+    pub barcode_kind: Option<String>,
+    // forms of the barcode sent to providers.
+    pub barcode_forms: Vec<String>,
+    pub route: Option<String>,
+    pub cover_exists: bool,
+    pub provider: Option<String>,
+    pub fuzzy: bool,
+    pub last_try: Option<NaiveDateTime>,
+    pub next_try_at: Option<NaiveDateTime>,
+    pub attempts: u32,
+    pub outcome: Option<OutcomeKind>,
+    pub failure: Option<String>,
+    pub decision: Decision,
+    // why the decision was made.
+    pub reason: String,
 }
+
+/// explain if retrieve_cover would retrieve the cover of the product, without retrieving it.
+pub async fn retrieval_status(
+    conn: &Object,
+    client: &doli_client_api_rs::Client,
+    config: &Config,
+    product_id: u32,
+) -> Result<RetrievalStatus> {
+    let cover: Option<Cover> = conn
+        .interact(move |conn| covers.find(product_id).first(conn).optional())
+        .await
+        .map_err(|e| anyhow!(e.to_string()))??;
+    let now = Utc::now().naive_utc();
+    let mut status = RetrievalStatus {
+        product_exists: false,
+        barcode: None,
+        barcode_kind: None,
+        barcode_forms: Vec::new(),
+        route: None,
+        cover_exists: cover.as_ref().is_some_and(|c| c.provider.is_some()),
+        // covers retrieved before names were stored only have the code of their provider.
+        provider: cover.as_ref().and_then(|c| {
+            c.provider_name.clone().or_else(|| {
+                c.provider
+                    .and_then(CoverProvider::name_of_code)
+                    .map(String::from)
+            })
+        }),
+        fuzzy: cover.as_ref().is_some_and(|c| c.fuzzy),
+        last_try: cover.as_ref().map(|c| c.last_try),
+        next_try_at: cover.as_ref().and_then(|c| c.next_try_at),
+        attempts: cover.as_ref().map(|c| c.attempts).unwrap_or_default(),
+        outcome: cover
+            .as_ref()
+            .and_then(|c| c.outcome)
+            .and_then(OutcomeKind::from_code),
+        failure: cover.and_then(|c| c.failure),
+        decision: Decision::Retrieve,
+        reason: String::from("the cover will be retrieved"),
+    };
+    // same order of checks as retrieve_cover.
+    let product = match get_product(client, config, product_id).await {
+        // the product exists but what the providers need is unknown, the decision can not be told.
+        Err(e) if e.is::<ProductDetails>() => return Err(e),
+        product => product,
+    };
+    match &product {
+        Ok(product) => {
+            status.product_exists = true;
+            status.barcode = Some(product.barcode.code().to_string());
+            status.barcode_kind = Some(product.barcode.kind().to_string());
+            status.barcode_forms = product.barcode.forms();
+            status.route = Some(route_for(config, product).0.to_string());
+        }
+        Err(e) if e.is::<NoBarcode>() || e.is::<BarcodeError>() => {
+            status.product_exists = true;
+            status.barcode = client.get_barcode_from_id(product_id).await.ok().flatten();
+        }
+        Err(_) => (),
+    }
+    let (decision, reason) = if status.cover_exists {
+        (
+            Decision::CoverExists,
+            String::from("cover already exist for this product"),
+        )
+    } else if status.next_try_at.is_some_and(|n| n > now) {
+        (
+            Decision::Waiting,
+            String::from(
+                "wait time before trying to get the cover for this product did not expired",
+            ),
+        )
+    } else {
+        match product {
+            Ok(_) => (Decision::Retrieve, status.reason.clone()),
+            Err(e) if e.is::<NoBarcode>() && config.fuzzy_search.enabled => (
+                Decision::FuzzySearch,
+                String::from("the product does not have barcode, it will be searched by title"),
+            ),
+            Err(e) if e.is::<NoBarcode>() => (Decision::NoBarcode, e.to_string()),
+            Err(e) if e.is::<BarcodeError>() => (Decision::InvalidBarcode, format!("{e:#}")),
+            Err(e) => (Decision::ProductNotFound, format!("{e:#}")),
+        }
+    };
+    status.decision = decision;
+    status.reason = reason;
+    Ok(status)
+}
+
+/// get from the product API what the providers need to find the cover.
+pub async fn get_product(
+    client: &doli_client_api_rs::Client,
+    config: &Config,
+    product_id: u32,
+) -> Result<Product> {
+    let barcode = get_barcode(client, product_id).await?;
+    let barcode =
+        Barcode::parse(&barcode).with_context(|| format!("invalid barcode \"{barcode}\""))?;
+    debug!(
+        "barcode {} of product {product_id} is of kind {}",
+        barcode.code(),
+        barcode.kind()
+    );
+    // the reference is only asked if a provider use it.
+    let reference = if config
+        .providers
+        .iter()
+        .any(|p| matches!(&p.provider, CoverProvider::Template(t) if t.need_ref()))
+    {
+        Some(
+            client
+                .get_ref_from_id(product_id)
+                .await
+                .context(ProductDetails)?,
+        )
+    } else {
+        None
+    };
+    // categories are only asked if a provider or a route is restricted to some of them.
+    let categories = if config.providers.iter().any(|p| !p.categories.is_empty())
+        || config.routes.iter().any(|r| !r.categories.is_empty())
+    {
+        client
+            .get_categories_from_id(product_id)
+            .await
+            .context(ProductDetails)?
+    } else {
+        Vec::new()
+    };
+    let product_type = if config.routes.iter().any(|r| !r.product_types.is_empty()) {
+        Some(
+            client
+                .get_type_from_id(product_id)
+                .await
+                .context(ProductDetails)?,
+        )
+    } else {
+        None
+    };
+    Ok(Product {
+        barcode,
+        reference,
+        categories,
+        product_type,
+    })
+}
+/// the product API failed to give the reference, categories or type of a product having a barcode.
+#[derive(Error, Debug)]
+#[error("the product API failed to give the details of the product")]
+struct ProductDetails;
+
+/// the product does not have a barcode in the product API.
+#[derive(Error, Debug)]
+#[error("this product does not have barcode. Enable fuzzy_search to search its cover by title.")]
+pub struct NoBarcode;
+
 async fn get_barcode(client: &doli_client_api_rs::Client, product_id: u32) -> Result<String> {
-    client.get_barcode_from_id(product_id).await?.context("this product does not have barcode. Only products with barcode are supported in this version.")
+    Ok(client
+        .get_barcode_from_id(product_id)
+        .await?
+        .ok_or(NoBarcode)?)
 }
 /// verify with the cover API DB if conditions are met to retrieve the cover.
 /// In case the id exist in the table, it will check if the id already has an image or if the delay for retrying is expired.
 /// Return an error in case the product should not get retrieved.
-async fn get_status_must_get_image(conn: &Object, product_id: u32, wait_retry: u64) -> Result<()> {
+async fn get_status_must_get_image(conn: &Object, product_id: u32) -> Result<()> {
     // does the Cover DB posses a row with this id ?
     if id_exist(conn, product_id).await? {
         if cover_exist(conn, product_id).await? {
@@ -80,9 +307,7 @@ async fn get_status_must_get_image(conn: &Object, product_id: u32, wait_retry: u
             return Err(anyhow!("cover already exist for this product"));
         }
         // if no, did the last try expired
-        if last_try(conn, product_id).await? + Duration::from_secs(wait_retry)
-            <= Utc::now().naive_utc()
-        {
+        if !retry_expired(conn, product_id).await? {
             return Err(anyhow!(
                 "wait time before trying to get the cover for this product did not expired"
             ));
@@ -91,13 +316,35 @@ async fn get_status_must_get_image(conn: &Object, product_id: u32, wait_retry: u
     Ok(())
 }
 
+/// a cover uploaded manually is not replaced unless asked.
+async fn check_manual_protected(
+    conn: &Object,
+    product_id: u32,
+    options: RetrieveOptions,
+) -> Result<()> {
+    let manual = CoverProvider::Manual.code();
+    let is_manual = conn
+        .interact(move |conn| {
+            select(exists(
+                covers.filter(id.eq(product_id).and(provider.eq(manual))),
+            ))
+            .get_result(conn)
+        })
+        .await
+        .map_err(|e| anyhow!(e.to_string()))??;
+    if is_manual && !options.override_manual {
+        bail!("the cover of this product was uploaded manually, set override_manual to replace it");
+    }
+    Ok(())
+}
+
 async fn id_exist(conn: &Object, product_id: u32) -> Result<bool> {
     Ok(conn
         .interact(move |conn| select(exists(covers.filter(id.eq(product_id)))).get_result(conn))
         .await
         .map_err(|e| anyhow!(e.to_string()))??)
 }
-async fn cover_exist(conn: &Object, product_id: u32) -> Result<bool> {
+pub async fn cover_exist(conn: &Object, product_id: u32) -> Result<bool> {
     Ok(conn
         .interact(move |conn| {
             select(exists(
@@ -108,32 +355,85 @@ async fn cover_exist(conn: &Object, product_id: u32) -> Result<bool> {
         .await
         .map_err(|e| anyhow!(e.to_string()))??)
 }
-pub async fn all_id(conn: &Object) -> Result<Vec<u32>> {
+async fn retry_expired(conn: &Object, product_id: u32) -> Result<bool> {
+    let now = Utc::now().naive_utc();
     Ok(conn
-        .interact(move |conn| covers.select(id).load(conn))
+        .interact(move |conn| {
+            select(exists(
+                covers.filter(
+                    id.eq(product_id)
+                        .and(next_try_at.is_null().or(next_try_at.le(now))),
+                ),
+            ))
+            .get_result(conn)
+        })
         .await
         .map_err(|e| anyhow!(e.to_string()))??)
 }
-pub async fn all_id_missing_retrievable(conn: &Object, wait_try: u64) -> Result<Vec<u32>> {
-    let mut covers_id: Vec<Cover> = conn
-        .interact(move |conn| covers.filter(provider.is_null()).load::<Cover>(conn))
+pub async fn all_id(conn: &Object) -> Result<Vec<u32>> {
+    Ok(conn
+        .interact(move |conn| covers.select(id).load(conn))
         .await
-        .map_err(|e| anyhow!(e.to_string()))??;
-    covers_id.retain(|c| c.last_try + Duration::from_secs(wait_try) <= Utc::now().naive_utc());
-    Ok(covers_id.iter().map(|c| c.id).collect())
+        .map_err(|e| anyhow!(e.to_string()))??)
 }
-async fn last_try(conn: &Object, product_id: u32) -> Result<NaiveDateTime> {
+pub async fn all_id_missing_retrievable(conn: &Object) -> Result<Vec<u32>> {
+    let now = Utc::now().naive_utc();
     Ok(conn
         .interact(move |conn| {
             covers
-                .select(schema::covers::last_try)
-                .find(product_id)
-                .first(conn)
+                .select(id)
+                .filter(provider.is_null())
+                .filter(next_try_at.is_null().or(next_try_at.le(now)))
+                .load(conn)
         })
         .await
         .map_err(|e| anyhow!(e.to_string()))??)
 }
 
+/// verify that the wait grows after each retrieval without cover.
+pub fn check_backoff(factor: f64) -> Result<()> {
+    // NaN fails the comparison too.
+    if !(factor.is_finite() && factor >= 1.0) {
+        bail!("retry_backoff_factor {factor} must be a finite number of at least 1");
+    }
+    Ok(())
+}
+
+/// retrievals without cover in a row and the date of the next retrieval after this outcome.
+/// Only providers answering without cover, or with a placeholder, count as a retrieval without cover.
+/// A provider failing to answer is retried sooner, a provider refusing the request after a fixed time.
+fn next_try(
+    config: &Config,
+    outcome_kind: Option<OutcomeKind>,
+    previous_attempts: u32,
+) -> (u32, Option<NaiveDateTime>) {
+    let now = Utc::now().naive_utc();
+    let (attempts, wait) = match outcome_kind {
+        Some(OutcomeKind::Found) => return (0, None),
+        Some(OutcomeKind::TransientError) => {
+            (previous_attempts, config.wait_seconds_retry_transient)
+        }
+        // a revoked key or a change of the provider API must not push back every product.
+        Some(OutcomeKind::PermanentError) => {
+            (previous_attempts, config.wait_seconds_retry_permanent)
+        }
+        // a deleted cover waits as much as a first retrieval.
+        None => (0, config.wait_seconds_retry_retrieve_cover),
+        Some(OutcomeKind::NotFound | OutcomeKind::Rejected) => {
+            let attempts = previous_attempts.saturating_add(1);
+            let wait = config.wait_seconds_retry_retrieve_cover as f64
+                * config
+                    .retry_backoff_factor
+                    .powi(attempts.saturating_sub(1).min(i32::MAX as u32) as i32);
+            (
+                attempts,
+                wait.min(config.wait_seconds_retry_max as f64) as u64,
+            )
+        }
+    };
+    (attempts, Some(now + Duration::from_secs(wait)))
+}
+
 // does the Cover DB posses a row with this id ?
 // if yes, does the cover already exist ?
 // if yes, error cover already exist
@@ -144,16 +444,42 @@ async fn last_try(conn: &Object, product_id: u32) -> Result<NaiveDateTime> {
 pub async fn update_table_image(
     product_id: u32,
     conn: &Object,
+    config: &Config,
     name_cp: Option<CoverProvider>,
+    outcome_kind: Option<OutcomeKind>,
+    // reason why the cover could not be retrieved.
+    failure_reason: Option<String>,
 ) -> Result<(), AppError> {
     use crate::schema::covers::dsl::*;
     use diesel::prelude::*;
     let now = Utc::now().naive_utc();
-    let provider_code = name_cp.map(|p| p as u8);
+    let previous_attempts: u32 = conn
+        .interact(move |conn| {
+            covers
+                .select(attempts)
+                .find(product_id)
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(|_| AppError::Backend)?
+        .map_err(|_| AppError::Backend)?
+        .unwrap_or_default();
+    let (attempts_count, next_try) = next_try(config, outcome_kind, previous_attempts);
     let record = Cover {
         id: product_id,
         last_try: now,
-        provider: provider_code,
+        provider: name_cp.as_ref().map(|p| p.code()),
+        failure: failure_reason,
+        fuzzy: false,
+        outcome: outcome_kind.map(|o| o.code()),
+        attempts: attempts_count,
+        next_try_at: next_try,
+        // set by set_original once the cover is written.
+        original_type: None,
+        original_width: None,
+        original_height: None,
+        provider_name: name_cp.map(|p| p.to_string()),
     };
     conn.interact(move |conn| diesel::replace_into(covers).values(&record).execute(conn))
         .await
@@ -161,3 +487,95 @@ pub async fn update_table_image(
         .map_err(|_| AppError::Backend)?;
     Ok(())
 }
+
+/// record the image from which the covers of the product were written.
+pub async fn set_original(conn: &Object, product_id: u32, original: Original) -> Result<()> {
+    conn.interact(move |conn| {
+        diesel::update(covers.find(product_id))
+            .set((
+                schema::covers::original_type.eq(original.content_type),
+                schema::covers::original_width.eq(original.width),
+                schema::covers::original_height.eq(original.height),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|e| anyhow!(e.to_string()))??;
+    Ok(())
+}
+
+/// ids of products whose covers can be written again from the original.
+pub async fn all_id_with_original(conn: &Object) -> Result<Vec<u32>> {
+    Ok(conn
+        .interact(|conn| {
+            covers
+                .filter(schema::covers::original_type.is_not_null())
+                .select(id)
+                .load(conn)
+        })
+        .await
+        .map_err(|e| anyhow!(e.to_string()))??)
+}
+
+/// mark the cover of the product as found by a fuzzy match, to be reviewed by the staff.
+#[cfg(feature = "openlibrary")]
+pub async fn set_fuzzy(conn: &Object, product_id: u32) -> Result<()> {
+    conn.interact(move |conn| {
+        diesel::update(covers.find(product_id))
+            .set(schema::covers::fuzzy.eq(true))
+            .execute(conn)
+    })
+    .await
+    .map_err(|e| anyhow!(e.to_string()))??;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // attempts and seconds to wait given by next_try.
+    fn wait(outcome_kind: Option<OutcomeKind>, previous_attempts: u32) -> (u32, Option<i64>) {
+        let config = Config::default();
+        let now = Utc::now().naive_utc();
+        let (attempts, next) = next_try(&config, outcome_kind, previous_attempts);
+        // rounded to the second, the time passes during the call.
+        (
+            attempts,
+            next.map(|n| (n - now).num_milliseconds().div_euclid(1000)),
+        )
+    }
+
+    #[test]
+    fn missing_covers_back_off() {
+        assert_eq!(wait(Some(OutcomeKind::NotFound), 0), (1, Some(3600)));
+        assert_eq!(wait(Some(OutcomeKind::NotFound), 1), (2, Some(7200)));
+        assert_eq!(wait(Some(OutcomeKind::Rejected), 3), (4, Some(28800)));
+        assert_eq!(wait(Some(OutcomeKind::NotFound), 20), (21, Some(2592000)));
+        assert_eq!(
+            wait(Some(OutcomeKind::NotFound), u32::MAX),
+            (u32::MAX, Some(2592000))
+        );
+    }
+
+    #[test]
+    fn errors_keep_the_attempts() {
+        assert_eq!(wait(Some(OutcomeKind::TransientError), 3), (3, Some(300)));
+        assert_eq!(wait(Some(OutcomeKind::PermanentError), 3), (3, Some(86400)));
+    }
+
+    #[test]
+    fn found_or_deleted_covers_reset_the_attempts() {
+        assert_eq!(wait(Some(OutcomeKind::Found), 5), (0, None));
+        assert_eq!(wait(None, 5), (0, Some(3600)));
+    }
+
+    #[test]
+    fn backoff_must_grow() {
+        assert!(check_backoff(Config::default().retry_backoff_factor).is_ok());
+        assert!(check_backoff(1.0).is_ok());
+        for factor in [0.5, 0.0, -2.0, f64::NAN, f64::INFINITY] {
+            assert!(check_backoff(factor).is_err());
+        }
+    }
+}
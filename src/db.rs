@@ -14,6 +14,41 @@ pub struct Cover {
     pub id: u32,
     pub last_try: NaiveDateTime,
     pub provider: Option<u8>,
+    pub failure: Option<String>,
+    // the cover was found by title and author and should be reviewed.
+    pub fuzzy: bool,
+    // outcome of the last retrieval, see OutcomeKind.
+    pub outcome: Option<u8>,
+    // retrievals in a row without cover, reset when a cover is found.
+    pub attempts: u32,
+    // the cover is retrieved again only after this date.
+    pub next_try_at: Option<NaiveDateTime>,
+    // content type and dimensions of the image from which the covers were written.
+    pub original_type: Option<String>,
+    pub original_width: Option<u32>,
+    pub original_height: Option<u32>,
+    // name of the provider, providers defined by a template share their code.
+    pub provider_name: Option<String>,
+}
+
+/// request made to a provider for a product.
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::cover_attempts)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct Attempt {
+    // set by the DB on insert.
+    #[diesel(skip_insertion)]
+    pub id: u64,
+    pub product_id: u32,
+    pub provider: String,
+    // form of the barcode sent to the provider.
+    pub barcode: String,
+    pub tried_at: NaiveDateTime,
+    pub http_status: Option<u16>,
+    // see OutcomeKind.
+    pub outcome: u8,
+    pub error: Option<String>,
+    pub duration_ms: u32,
 }
 
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
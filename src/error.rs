@@ -19,6 +19,18 @@ pub enum AppError {
     #[error("Backend required for cover API failed")]
     #[status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)]
     Backend,
+    #[error("No size of cover is configured")]
+    #[status(axum::http::StatusCode::NOT_FOUND)]
+    SizeUnknown,
+    #[error("Dimension is not allowed for resized covers")]
+    #[status(StatusCode::BAD_REQUEST)]
+    DimensionNotAllowed,
+    #[error("Format is not enabled")]
+    #[status(StatusCode::BAD_REQUEST)]
+    FormatNotEnabled,
+    #[error("Candidate does not exist or has expired")]
+    #[status(axum::http::StatusCode::NOT_FOUND)]
+    CandidateNotFound,
     #[error("Database connection issue")]
     #[status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)]
     Db(#[from] deadpool_diesel::PoolError),
@@ -32,6 +44,10 @@ impl AppError {
             Self::Backend => warn!("a backend service seems to be unjoinable"),
             Self::FileNotFound(_) => warn!("a file requested does not exist"),
             Self::Db(_) => warn!("a database was unjoinable"),
+            Self::SizeUnknown => warn!("a size of cover was requested but none is configured"),
+            Self::DimensionNotAllowed => warn!("a cover was requested in a dimension not allowed"),
+            Self::FormatNotEnabled => warn!("a cover was requested in a format not enabled"),
+            Self::CandidateNotFound => warn!("a candidate requested is not in memory anymore"),
             Self::Host => warn!("invalid value from HOST header for task tracker API"),
         }
         self
@@ -1,37 +1,634 @@
-use std::{fs::File, path::Path};
-
-use anyhow::Result;
-use image::{imageops::FilterType, load_from_memory};
-pub const RES_COVER_MINI: u32 = 45;
-pub const RES_COVER_SMALL: u32 = 135;
-pub const RES_COVER_ORIGIN: u32 = 240;
-pub fn write_cover(cover: &[u8], id: u32, path_cover: &Path) -> Result<()> {
-    let image = load_from_memory(cover)?;
-    // resize image
-    let filter_resize = FilterType::Lanczos3;
-    let cover_mini = image.resize(RES_COVER_MINI, RES_COVER_MINI, filter_resize);
-    let cover_small = image.resize(RES_COVER_SMALL, RES_COVER_SMALL, filter_resize);
-    let cover_origin = image.resize(RES_COVER_ORIGIN, RES_COVER_ORIGIN, filter_resize);
-
-    // write into path files
-
-    // write into mini image file
-    let mut path_mini = path_cover.to_path_buf();
-    path_mini.push(format!("{id}-M.webp"));
-    let mut file = File::create_new(path_mini)?;
-    cover_mini.write_to(&mut file, image::ImageFormat::WebP)?;
-
-    // write into small image file
-    let mut path_small = path_cover.to_path_buf();
-    path_small.push(format!("{id}-S.webp"));
-    let mut file = File::create_new(path_small)?;
-    cover_small.write_to(&mut file, image::ImageFormat::WebP)?;
-
-    // write into large image file
-    let mut path_origin = path_cover.to_path_buf();
-    path_origin.push(format!("{id}-L.webp"));
-    let mut file = File::create_new(path_origin)?;
-    cover_origin.write_to(&mut file, image::ImageFormat::WebP)?;
+use std::{
+    fs::{create_dir_all, read, remove_file, rename, write, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
 
+use anyhow::{bail, Result};
+use glob::{glob, Paths, Pattern};
+#[cfg(feature = "avif")]
+use image::codecs::avif::AvifEncoder;
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    guess_format,
+    imageops::{overlay, FilterType},
+    load_from_memory, load_from_memory_with_format, DynamicImage, GenericImageView, Rgba,
+    RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+
+// directory, inside the path of covers, of the images as sent by providers or the staff.
+const DIR_ORIGINALS: &str = "originals";
+// directory, inside the path of covers, of the covers resized on demand.
+const DIR_RESIZED: &str = "resized";
+
+/// size in which covers are written, served as /{id}/cover-{name}.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CoverSize {
+    pub name: String,
+    pub max_width: u32,
+    pub max_height: u32,
+    pub fit: Fit,
+    // colour around the image with the fit Contain, transparent by default.
+    #[serde(default)]
+    pub background: Background,
+    // part of the image kept with the fit Cover.
+    #[serde(default)]
+    pub crop: Crop,
+}
+
+/// how the image is resized to the size.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub enum Fit {
+    // the whole image fits in the size, keeping its aspect ratio.
+    #[serde(alias = "inside")]
+    Inside,
+    // the image fills the size, keeping its aspect ratio and cropping what is outside.
+    #[serde(alias = "cover")]
+    Cover,
+    // the whole image fits in the size, keeping its aspect ratio, and is centered on a background of the size.
+    #[serde(alias = "contain")]
+    Contain,
+    // the image is stretched to the size.
+    #[serde(alias = "fill")]
+    Fill,
+}
+
+/// colour written as #rrggbb or #rrggbbaa.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(try_from = "String", into = "String")]
+pub struct Background(Rgba<u8>);
+
+impl Default for Background {
+    fn default() -> Self {
+        Self(Rgba([255, 255, 255, 0]))
+    }
+}
+
+impl TryFrom<String> for Background {
+    type Error = String;
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let invalid = || format!("colour \"{value}\" must be written as #rrggbb or #rrggbbaa");
+        let hex = value.strip_prefix('#').ok_or_else(invalid)?;
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut channels = [255; 4];
+        for (i, channel) in channels.iter_mut().enumerate().take(hex.len() / 2) {
+            *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(Rgba(channels)))
+    }
+}
+
+impl From<Background> for String {
+    fn from(background: Background) -> Self {
+        let [r, g, b, a] = background.0 .0;
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+}
+
+/// part of the image kept when it is cropped to the size.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+pub enum Crop {
+    // the center of the image.
+    #[default]
+    #[serde(alias = "center")]
+    Center,
+    // the part of the image with the most details, most likely the title and illustration.
+    #[serde(alias = "smart")]
+    Smart,
+}
+
+impl Fit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Inside => "inside",
+            Self::Cover => "cover",
+            Self::Contain => "contain",
+            Self::Fill => "fill",
+        }
+    }
+}
+
+/// format in which covers are served.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum Format {
+    #[serde(alias = "webp")]
+    WebP,
+    #[serde(alias = "png")]
+    Png,
+    #[serde(alias = "jpeg", alias = "jpg")]
+    Jpeg,
+    #[cfg(feature = "avif")]
+    #[serde(alias = "avif")]
+    Avif,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::WebP => "image/webp",
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            #[cfg(feature = "avif")]
+            Self::Avif => "image/avif",
+        }
+    }
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::WebP => "webp",
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            #[cfg(feature = "avif")]
+            Self::Avif => "avif",
+        }
+    }
+}
+
+/// format enabled in the configuration, with its settings.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(tag = "format")]
+pub enum Encoding {
+    // lossless without quality, lossy with a quality from 1 to 100.
+    WebP {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        quality: Option<u8>,
+    },
+    Png,
+    // quality from 1 to 100.
+    Jpeg {
+        quality: u8,
+    },
+    // speed from 1 (slowest, smallest files) to 10 and quality from 1 to 100.
+    #[cfg(feature = "avif")]
+    Avif {
+        speed: u8,
+        quality: u8,
+    },
+}
+
+impl Encoding {
+    /// formats by default, WebP being preferred.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::WebP { quality: None },
+            Self::Jpeg { quality: 85 },
+            Self::Png,
+        ]
+    }
+    pub fn format(&self) -> Format {
+        match self {
+            Self::WebP { .. } => Format::WebP,
+            Self::Png => Format::Png,
+            Self::Jpeg { .. } => Format::Jpeg,
+            #[cfg(feature = "avif")]
+            Self::Avif { .. } => Format::Avif,
+        }
+    }
+    fn encode(&self, image: &DynamicImage) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match *self {
+            Self::WebP { quality: None } => {
+                image.write_with_encoder(WebPEncoder::new_lossless(&mut bytes))?
+            }
+            Self::WebP {
+                quality: Some(quality),
+            } => {
+                let image = image.to_rgba8();
+                bytes = webp::Encoder::from_rgba(&image, image.width(), image.height())
+                    .encode(quality.into())
+                    .to_vec();
+            }
+            Self::Png => image.write_with_encoder(PngEncoder::new(&mut bytes))?,
+            // JPEG has no transparency.
+            Self::Jpeg { quality } => DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, quality))?,
+            #[cfg(feature = "avif")]
+            Self::Avif { speed, quality } => DynamicImage::ImageRgba8(image.to_rgba8())
+                .write_with_encoder(AvifEncoder::new_with_speed_quality(
+                    &mut bytes, speed, quality,
+                ))?,
+        }
+        Ok(bytes)
+    }
+}
+
+/// WebP settings in which covers are stored, lossless if WebP is not enabled.
+pub fn webp_encoding(formats: &[Encoding]) -> Encoding {
+    formats
+        .iter()
+        .find(|e| e.format() == Format::WebP)
+        .copied()
+        .unwrap_or(Encoding::WebP { quality: None })
+}
+
+/// verify the formats enabled and their settings.
+pub fn check_formats(formats: &[Encoding]) -> Result<()> {
+    if formats.is_empty() {
+        bail!("at least one format is required");
+    }
+    for (i, encoding) in formats.iter().enumerate() {
+        let content_type = encoding.format().content_type();
+        if formats[..i].iter().any(|e| e.format() == encoding.format()) {
+            bail!("format {content_type} is present twice");
+        }
+        let valid = match *encoding {
+            Encoding::WebP { quality } => quality.is_none_or(|q| (1..=100).contains(&q)),
+            Encoding::Png => true,
+            Encoding::Jpeg { quality } => (1..=100).contains(&quality),
+            #[cfg(feature = "avif")]
+            Encoding::Avif { speed, quality } => {
+                (1..=10).contains(&speed) && (1..=100).contains(&quality)
+            }
+        };
+        if !valid {
+            bail!("settings of format {content_type} are out of range");
+        }
+    }
+    Ok(())
+}
+
+/// dimensions allowed for covers resized on demand.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct Dimension {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Dimension {
+    /// dimensions by default, for covers shown in lists, pages and zoomed.
+    pub fn defaults() -> Vec<Self> {
+        [(90, 135), (200, 300), (300, 450), (600, 900)]
+            .into_iter()
+            .map(|(width, height)| Self { width, height })
+            .collect()
+    }
+}
+
+impl CoverSize {
+    fn new(name: &str, max: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            max_width: max,
+            max_height: max,
+            fit: Fit::Inside,
+            background: Background::default(),
+            crop: Crop::Center,
+        }
+    }
+    /// size of covers resized on demand, on a transparent background and cropped at the center.
+    pub fn on_demand(dimension: Dimension, fit: Fit) -> Self {
+        Self {
+            name: String::new(),
+            max_width: dimension.width,
+            max_height: dimension.height,
+            fit,
+            background: Background::default(),
+            crop: Crop::Center,
+        }
+    }
+    /// sizes by default, the same as before they were configurable.
+    /// The 45px covers were written as M and the 135px as S, the names are kept for the existing files and urls.
+    pub fn defaults() -> Vec<Self> {
+        vec![Self::new("M", 45), Self::new("S", 135), Self::new("L", 240)]
+    }
+    /// name of the file of the cover of this size.
+    pub fn file_name(&self, id: u32) -> String {
+        format!("{id}-{}.webp", self.name)
+    }
+    /// resize the image to the size. Except with the fit Inside, the image has exactly the dimensions of the size.
+    fn resize(&self, image: &DynamicImage) -> DynamicImage {
+        let (width, height) = (self.max_width, self.max_height);
+        let filter_resize = FilterType::Lanczos3;
+        match (self.fit, self.crop) {
+            (Fit::Inside, _) => image.resize(width, height, filter_resize),
+            (Fit::Cover, Crop::Center) => image.resize_to_fill(width, height, filter_resize),
+            (Fit::Cover, Crop::Smart) => smart_crop(image, width, height),
+            (Fit::Contain, _) => {
+                let resized = image.resize(width, height, filter_resize);
+                let mut canvas = RgbaImage::from_pixel(width, height, self.background.0);
+                let x = (width - resized.width()) / 2;
+                let y = (height - resized.height()) / 2;
+                overlay(&mut canvas, &resized, x.into(), y.into());
+                DynamicImage::ImageRgba8(canvas)
+            }
+            (Fit::Fill, _) => image.resize_exact(width, height, filter_resize),
+        }
+    }
+}
+
+/// fill the size and crop the part of the image with the most edges, instead of its center.
+fn smart_crop(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let scale = f64::max(
+        width as f64 / image.width() as f64,
+        height as f64 / image.height() as f64,
+    );
+    let scaled_width = ((image.width() as f64 * scale).round() as u32).max(width);
+    let scaled_height = ((image.height() as f64 * scale).round() as u32).max(height);
+    let scaled = image.resize_exact(scaled_width, scaled_height, FilterType::Lanczos3);
+    // edges of every column, or every row, along the side to crop.
+    let luma = scaled.to_luma8();
+    let horizontal = scaled_width > width;
+    let mut edges = vec![
+        0u64;
+        if horizontal {
+            scaled_width
+        } else {
+            scaled_height
+        } as usize
+    ];
+    for (x, y, pixel) in luma.enumerate_pixels() {
+        let right = luma.get_pixel(x.saturating_sub(1), y);
+        let below = luma.get_pixel(x, y.saturating_sub(1));
+        let edge = pixel[0].abs_diff(right[0]) as u64 + pixel[0].abs_diff(below[0]) as u64;
+        edges[if horizontal { x } else { y } as usize] += edge;
+    }
+    let window = if horizontal { width } else { height } as usize;
+    // with the same edges, the window closest to the center is kept.
+    let center = (edges.len() - window) / 2;
+    let offset = (0..=edges.len() - window)
+        .map(|offset| (edges[offset..offset + window].iter().sum::<u64>(), offset))
+        .max_by_key(|(sum, offset)| (*sum, std::cmp::Reverse(offset.abs_diff(center))))
+        .map_or(0, |(_, offset)| offset as u32);
+    let (x, y) = if horizontal { (offset, 0) } else { (0, offset) };
+    DynamicImage::from(scaled.view(x, y, width, height).to_image())
+}
+
+/// verify the sizes can be used in file names and urls.
+pub fn check_sizes(sizes: &[CoverSize]) -> Result<()> {
+    if sizes.is_empty() {
+        bail!("at least one size of cover is required");
+    }
+    for (i, size) in sizes.iter().enumerate() {
+        if size.name.is_empty()
+            || !size
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            bail!(
+                "size name \"{}\" must only contain letters, digits and _",
+                size.name
+            );
+        }
+        if size.max_width == 0 || size.max_height == 0 {
+            bail!("size {} must have a width and height", size.name);
+        }
+        if sizes[..i].iter().any(|s| s.name == size.name) {
+            bail!("size {} is present twice", size.name);
+        }
+    }
+    Ok(())
+}
+
+/// verify the dimensions allowed for covers resized on demand are not empty.
+pub fn check_dimensions(dimensions: &[Dimension]) -> Result<()> {
+    if let Some(d) = dimensions.iter().find(|d| d.width == 0 || d.height == 0) {
+        bail!(
+            "dimension {}x{} must have a width and height",
+            d.width,
+            d.height
+        );
+    }
     Ok(())
 }
+
+/// size of the name given, or the largest one.
+pub fn size_or_largest<'a>(sizes: &'a [CoverSize], name: &str) -> Option<&'a CoverSize> {
+    sizes.iter().find(|s| s.name == name).or_else(|| {
+        sizes
+            .iter()
+            .max_by_key(|s| s.max_width as u64 * s.max_height as u64)
+    })
+}
+
+/// image from which the covers of a product are written, kept as received.
+pub struct Original {
+    pub content_type: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// write the covers of every size and keep the original, replacing the existing ones.
+/// Files are written next to the covers and renamed only once every size is written, so a cover is never served half written.
+pub fn write_cover(
+    cover: &[u8],
+    id: u32,
+    path_cover: &Path,
+    sizes: &[CoverSize],
+    webp: Encoding,
+) -> Result<Original> {
+    let format = guess_format(cover)?;
+    let image = load_from_memory_with_format(cover, format)?;
+
+    // write into temporary files
+    let mut written = write_sizes_tmp(&image, id, path_cover, sizes, webp)?;
+    let path_originals = path_cover.join(DIR_ORIGINALS);
+    create_dir_all(&path_originals)?;
+    let tmp = path_originals.join(format!(".{id}.tmp"));
+    write(&tmp, cover)?;
+    written.push((tmp, original_path(path_cover, id)));
+
+    replace_covers(written, id, path_cover)?;
+    Ok(Original {
+        content_type: format.to_mime_type().to_string(),
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+/// write again the covers of every size from the original, after the sizes were changed.
+/// The files of sizes not configured anymore are deleted.
+pub fn regenerate_cover(
+    id: u32,
+    path_cover: &Path,
+    sizes: &[CoverSize],
+    webp: Encoding,
+) -> Result<()> {
+    let image = load_from_memory(&read(original_path(path_cover, id))?)?;
+    let written = write_sizes_tmp(&image, id, path_cover, sizes, webp)?;
+    replace_covers(written, id, path_cover)?;
+    let names = sizes.iter().map(|s| s.file_name(id)).collect::<Vec<_>>();
+    for path in matching(path_cover, &format!("{id}-*.webp"))? {
+        let path = path?;
+        if !path
+            .file_name()
+            .is_some_and(|n| names.iter().any(|name| n == name.as_str()))
+        {
+            remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_sizes_tmp(
+    image: &DynamicImage,
+    id: u32,
+    path_cover: &Path,
+    sizes: &[CoverSize],
+    webp: Encoding,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    sizes
+        .iter()
+        .map(|size| {
+            let cover = webp.encode(&size.resize(image))?;
+            write_tmp(&cover, path_cover, &size.file_name(id))
+        })
+        .collect()
+}
+
+/// replace the covers by the temporary files written.
+fn replace_covers(written: Vec<(PathBuf, PathBuf)>, id: u32, path_cover: &Path) -> Result<()> {
+    for (tmp, path) in written {
+        rename(tmp, path)?;
+    }
+    // covers resized from the previous original are outdated.
+    delete_resized(id, path_cover)
+}
+
+/// path of the image from which the covers of the product were written.
+pub fn original_path(path_cover: &Path, id: u32) -> PathBuf {
+    path_cover.join(DIR_ORIGINALS).join(id.to_string())
+}
+
+/// path of a cover rendered on demand, which is also its key in the cache.
+/// Names of covers of a product start with its id, so they are deleted with the cover.
+pub fn resized_path(path_cover: &Path, name: &str, format: Format) -> PathBuf {
+    path_cover
+        .join(DIR_RESIZED)
+        .join(format!("{name}.{}", format.extension()))
+}
+
+/// resize the original to the size and encode it.
+pub fn render(original: &[u8], size: &CoverSize, encoding: Encoding) -> Result<Vec<u8>> {
+    encoding.encode(&size.resize(&load_from_memory(original)?))
+}
+
+/// keep a cover rendered on demand so it is rendered only once.
+pub fn write_resized(path: &Path, resized: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    write(&tmp, resized)?;
+    rename(tmp, path)?;
+    Ok(())
+}
+
+/// delete the covers of the product rendered on demand.
+pub fn delete_resized(id: u32, path_cover: &Path) -> Result<()> {
+    delete_matching(&path_cover.join(DIR_RESIZED), &format!("{id}-*"))
+}
+
+/// delete every cover rendered on demand, default covers included, outdated by new sizes or formats.
+pub fn delete_all_resized(path_cover: &Path) -> Result<()> {
+    delete_matching(&path_cover.join(DIR_RESIZED), "*")
+}
+
+fn delete_matching(dir: &Path, pattern: &str) -> Result<()> {
+    for path in matching(dir, pattern)? {
+        let path = path?;
+        // a file being written is renamed by its writer.
+        if path.extension().is_some_and(|e| e == "tmp") {
+            continue;
+        }
+        match remove_file(path) {
+            // deleted by a concurrent request.
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// files of the directory matching the pattern, the directory itself must not be read as a pattern.
+fn matching(dir: &Path, pattern: &str) -> Result<Paths> {
+    let dir = Pattern::escape(&dir.to_string_lossy());
+    Ok(glob(&[&dir, "/", pattern].concat())?)
+}
+
+/// write the image in a temporary file, return it with the path of the cover to replace.
+fn write_tmp(cover: &[u8], path_cover: &Path, name: &str) -> Result<(PathBuf, PathBuf)> {
+    let path = path_cover.join(name);
+    let tmp = path_cover.join(format!(".{name}.tmp"));
+    let mut file = File::create(&tmp)?;
+    file.write_all(cover)?;
+    file.sync_all()?;
+    Ok((tmp, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    fn size(max_width: u32, max_height: u32, fit: Fit, crop: Crop) -> CoverSize {
+        CoverSize {
+            crop,
+            ..CoverSize::on_demand(
+                Dimension {
+                    width: max_width,
+                    height: max_height,
+                },
+                fit,
+            )
+        }
+    }
+
+    #[test]
+    fn every_fit_gives_the_dimensions() {
+        for (width, height) in [(30, 60), (60, 30), (1, 50), (7, 7)] {
+            let image = DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+                Rgb([(x * 8) as u8, (y * 4) as u8, 128])
+            }));
+            for (max_width, max_height) in [(20, 20), (15, 40), (40, 15)] {
+                for (fit, crop) in [
+                    (Fit::Cover, Crop::Center),
+                    (Fit::Cover, Crop::Smart),
+                    (Fit::Contain, Crop::Center),
+                    (Fit::Fill, Crop::Center),
+                ] {
+                    let resized = size(max_width, max_height, fit, crop).resize(&image);
+                    assert_eq!(
+                        resized.dimensions(),
+                        (max_width, max_height),
+                        "{width}x{height} in {max_width}x{max_height} with {}",
+                        fit.name()
+                    );
+                }
+                // the image fits in the size, touching at least one of its sides.
+                let (w, h) = size(max_width, max_height, Fit::Inside, Crop::Center)
+                    .resize(&image)
+                    .dimensions();
+                assert!(w <= max_width && h <= max_height);
+                assert!(w == max_width || h == max_height);
+            }
+        }
+    }
+
+    #[test]
+    fn smart_crop_keeps_the_details() {
+        // a uniform image with a checkerboard on its left side.
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(40, 20, |x, y| {
+            if x < 6 {
+                Rgb([255 * ((x + y) % 2) as u8; 3])
+            } else {
+                Rgb([128; 3])
+            }
+        }));
+        let contrast = |image: &DynamicImage| {
+            let luma = image.to_luma8();
+            let values = luma.pixels().map(|p| p[0]);
+            values.clone().max().unwrap() - values.min().unwrap()
+        };
+        let smart = size(20, 20, Fit::Cover, Crop::Smart).resize(&image);
+        assert_eq!(smart.dimensions(), (20, 20));
+        assert!(contrast(&smart) > 200);
+        // the center of the image has no details.
+        let center = size(20, 20, Fit::Cover, Crop::Center).resize(&image);
+        assert!(contrast(&center) < 50);
+    }
+}
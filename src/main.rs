@@ -1,7 +1,8 @@
-use api::public::{get_cover, get_default_cover};
+use api::public::{get_cover, get_default_cover, get_resized_cover};
 use api::worker::{
-    add_manual_cover, delete_cover, get_missing_covers, retrieve_cover_handle,
-    retrieve_missing_covers,
+    add_manual_cover, choose_candidate, delete_cover, get_attempts, get_candidates,
+    get_missing_covers, get_providers_status, get_retrieval_status, preview_candidate,
+    regenerate_covers, retrieve_cover_handle, retrieve_covers, retrieve_missing_covers,
 };
 use axum::routing::delete;
 use axum::routing::get;
@@ -10,15 +11,26 @@ use axum::routing::put;
 use axum::Router;
 use deadpool_diesel::mysql::Manager;
 use deadpool_diesel::mysql::Pool;
-use get_pass::url::add_pass_to_url;
+use get_pass::{get_password, url::add_pass_to_url};
 use reqwest::Client;
 use std::error::Error;
 use tracing::info;
 
 use anyhow::Result;
+use attempt::prune_attempts;
+use candidate::CandidateStore;
 use config::Config;
+use cover::check_backoff;
 use db::run_migrations;
+use image::{check_dimensions, check_formats, check_sizes};
+use provider::{check_providers, check_routes};
 mod api;
+/// history of the requests made to providers
+mod attempt;
+/// validation and conversion of barcodes
+mod barcode;
+/// covers found by providers waiting to be chosen
+mod candidate;
 mod config;
 /// cover module contains everything related to the task created by the API interacting with the DB and product API
 mod cover;
@@ -26,6 +38,8 @@ mod db;
 /// Error from handler
 mod error;
 mod image;
+/// detection of placeholder images sent by providers
+mod placeholder;
 /// method to get cover from provider
 mod provider;
 mod schema;
@@ -39,6 +53,8 @@ struct AppState {
     client_task: tasks_tracker_client::Client,
     client_product: doli_client_api_rs::Client,
     client_cache: Client,
+    // covers proposed to the staff, not saved yet.
+    candidates: CandidateStore,
 }
 
 #[tokio::main]
@@ -46,7 +62,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
     tracing_subscriber::fmt::init();
     // config file with database url
     info!("loading config file");
-    let config: Config = confy::load("covers-api", "covers-api")?;
+    let mut config: Config = confy::load("covers-api", "covers-api")?;
+    // refuse providers that can not be used by this build.
+    check_providers(&config.providers)?;
+    check_routes(&config.routes, &config.providers)?;
+    config.placeholder.check()?;
+    check_sizes(&config.sizes)?;
+    check_dimensions(&config.resize_dimensions)?;
+    check_formats(&config.formats)?;
+    config.fuzzy_search.check()?;
+    check_backoff(config.retry_backoff_factor)?;
+    // get the API keys of providers.
+    for settings in config.providers.iter_mut() {
+        if let Some(path) = &settings.api_key_path_pass {
+            settings.api_key = Some(get_password(path)?);
+        }
+    }
 
     // construct the url of database connection.
     info!("connection to the DB");
@@ -82,12 +113,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
         client_task: client_tasks_tracker,
         client_product,
         client_cache,
+        candidates: CandidateStore::default(),
     };
     info!("checking and constructing tables");
     // create table if needed
     run_migrations(&state.conn_db_cover)
         .await
         .expect("failed to create table or connect to database.");
+    let conn = state.conn_db_cover.get().await?;
+    prune_attempts(&conn, state.config.attempts_retention_days).await?;
 
     // set up the API endpoints
     let adr = format!("127.0.0.1:{}", state.config.listen_port);
@@ -101,10 +135,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
 fn routes(state: AppState) -> Router {
     Router::new()
         .route("/:id/cover-:size", get(get_cover))
+        .route("/:id/cover", get(get_resized_cover))
         .route("/cover-default-:size", get(get_default_cover))
         .route("/:id/retreive-cover", put(retrieve_cover_handle))
+        // spelling of the documentation, the first one is kept for existing clients.
+        .route("/:id/retrieve-cover", put(retrieve_cover_handle))
         .route("/missing-covers", put(retrieve_missing_covers))
+        .route("/retrieve-covers", put(retrieve_covers))
+        .route("/regenerate-covers", put(regenerate_covers))
         .route("/missing-covers", get(get_missing_covers))
+        .route("/providers/status", get(get_providers_status))
+        .route("/:id/attempts", get(get_attempts))
+        .route("/:id/retrieval-status", get(get_retrieval_status))
+        .route("/:id/candidates", get(get_candidates))
+        .route("/:id/candidates/:candidate", post(choose_candidate))
+        .route("/:id/candidates/:candidate/preview", get(preview_candidate))
         .route("/:id", post(add_manual_cover))
         .route("/:id", delete(delete_cover))
         .with_state(state)
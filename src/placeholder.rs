@@ -0,0 +1,178 @@
+use image::{imageops::FilterType, load_from_memory, DynamicImage};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// rules to refuse the generic images some providers send instead of a cover.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PlaceholderConfig {
+    // images smaller than this resolution are refused.
+    pub min_width: u32,
+    pub min_height: u32,
+    // images with a standard deviation of luminance (0-255) lower than this are considered uniform.
+    pub min_deviation: f32,
+    // perceptual hashes (hexadecimal) of known placeholders, logged when an image is refused.
+    pub deny_hashes: Vec<String>,
+    // number of different bits for two hashes to be considered the same image.
+    pub max_hash_distance: u32,
+}
+
+impl Default for PlaceholderConfig {
+    fn default() -> Self {
+        Self {
+            min_width: 50,
+            min_height: 50,
+            min_deviation: 4.0,
+            deny_hashes: vec![],
+            max_hash_distance: 4,
+        }
+    }
+}
+
+/// properties of an image accepted as a cover.
+pub struct CoverInfo {
+    pub width: u32,
+    pub height: u32,
+    pub hash: u64,
+}
+
+/// reason for an image to be refused as a cover.
+#[derive(Error, Debug)]
+pub enum Rejected {
+    #[error("image can not be decoded")]
+    Decode(#[from] image::ImageError),
+    #[error("image of {0}x{1} is smaller than the minimum resolution")]
+    Resolution(u32, u32),
+    #[error("image is uniform, standard deviation of {0:.1}")]
+    Uniform(f32),
+    #[error("image is a known placeholder, hash {0:016x}")]
+    Denied(u64),
+}
+
+impl PlaceholderConfig {
+    /// verify that the hashes of the configuration are valid.
+    pub fn check(&self) -> anyhow::Result<()> {
+        for hash in &self.deny_hashes {
+            u64::from_str_radix(hash, 16)
+                .map_err(|e| anyhow::anyhow!("invalid placeholder hash {hash}: {e}"))?;
+        }
+        Ok(())
+    }
+    /// refuse the image if it looks like a placeholder instead of a cover.
+    /// The hash of the image is returned, to be added to the configuration if a placeholder is not detected.
+    pub fn check_cover(&self, cover: &[u8]) -> Result<CoverInfo, Rejected> {
+        let image = load_from_memory(cover)?;
+        if image.width() < self.min_width || image.height() < self.min_height {
+            return Err(Rejected::Resolution(image.width(), image.height()));
+        }
+        let deviation = deviation(&image);
+        if deviation < self.min_deviation {
+            return Err(Rejected::Uniform(deviation));
+        }
+        let hash = dhash(&image);
+        if self
+            .deny_hashes
+            .iter()
+            .filter_map(|h| u64::from_str_radix(h, 16).ok())
+            .any(|h| (h ^ hash).count_ones() <= self.max_hash_distance)
+        {
+            return Err(Rejected::Denied(hash));
+        }
+        Ok(CoverInfo {
+            width: image.width(),
+            height: image.height(),
+            hash,
+        })
+    }
+}
+
+/// standard deviation of the luminance, on a reduced image.
+fn deviation(image: &DynamicImage) -> f32 {
+    let luma = image.resize(64, 64, FilterType::Triangle).into_luma8();
+    let n = luma.len() as f32;
+    let mean = luma.iter().map(|p| *p as f32).sum::<f32>() / n;
+    (luma.iter().map(|p| (*p as f32 - mean).powi(2)).sum::<f32>() / n).sqrt()
+}
+
+/// difference hash: each bit tells if a pixel is brighter than its right neighbour on a 9x8 image.
+fn dhash(image: &DynamicImage) -> u64 {
+    let luma = image.resize_exact(9, 8, FilterType::Triangle).into_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if luma.get_pixel(x, y)[0] > luma.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{GrayImage, ImageFormat, Luma};
+
+    use super::*;
+
+    // image whose pixels get darker from left to right.
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, _| {
+            Luma([255 - (x * 255 / width) as u8])
+        }))
+    }
+
+    fn png(image: &DynamicImage) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn dhash_compares_neighbours() {
+        assert_eq!(dhash(&gradient(90, 80)), u64::MAX);
+        assert_eq!(dhash(&gradient(90, 80).fliph()), 0);
+        // the hash does not depend on the resolution.
+        assert_eq!(dhash(&gradient(900, 800)), dhash(&gradient(90, 80)));
+    }
+
+    #[test]
+    fn deviation_of_uniform_and_contrasted_images() {
+        let uniform = DynamicImage::ImageLuma8(GrayImage::from_pixel(100, 100, Luma([128])));
+        assert_eq!(deviation(&uniform), 0.0);
+        let halves = DynamicImage::ImageLuma8(GrayImage::from_fn(100, 100, |x, _| {
+            Luma([if x < 50 { 0 } else { 255 }])
+        }));
+        assert!((deviation(&halves) - 127.5).abs() < 5.0);
+    }
+
+    #[test]
+    fn placeholders_are_rejected() {
+        let config = PlaceholderConfig::default();
+        assert!(matches!(
+            config.check_cover(b"not an image"),
+            Err(Rejected::Decode(_))
+        ));
+        assert!(matches!(
+            config.check_cover(&png(&gradient(40, 100))),
+            Err(Rejected::Resolution(40, 100))
+        ));
+        let uniform = DynamicImage::ImageLuma8(GrayImage::from_pixel(100, 100, Luma([200])));
+        assert!(matches!(
+            config.check_cover(&png(&uniform)),
+            Err(Rejected::Uniform(_))
+        ));
+        let info = config.check_cover(&png(&gradient(100, 150))).unwrap();
+        assert_eq!((info.width, info.height, info.hash), (100, 150, u64::MAX));
+        // a hash close enough to a denied one is the same image.
+        let config = PlaceholderConfig {
+            deny_hashes: vec![format!("{:016x}", u64::MAX ^ 0b111)],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.check_cover(&png(&gradient(100, 150))),
+            Err(Rejected::Denied(u64::MAX))
+        ));
+    }
+}
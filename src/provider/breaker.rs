@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::ProviderConfig;
+
+// circuit of every provider, by name.
+static CIRCUITS: Mutex<BTreeMap<String, Circuit>> = Mutex::new(BTreeMap::new());
+
+/// stop asking a provider that does not answer anymore.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct CircuitBreaker {
+    // consecutive transport errors opening the circuit.
+    pub threshold: u32,
+    // time in seconds before the provider is asked again.
+    pub cooldown_seconds: u64,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            cooldown_seconds: 300,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Circuit {
+    // the provider is used, with the count of consecutive transport errors.
+    Closed(u32),
+    // the provider is skipped until this instant.
+    Open(Instant),
+    // one request is made to check if the provider is back since this instant.
+    HalfOpen(Instant),
+}
+
+/// state of the circuit of a provider, for administrators.
+#[derive(Serialize)]
+pub struct CircuitStatus {
+    pub provider: String,
+    pub state: &'static str,
+    pub failures: u32,
+    // seconds before the provider is asked again when the circuit is open.
+    pub retry_in: Option<u64>,
+}
+
+impl CircuitBreaker {
+    /// can the provider be asked ? After the cooldown, only one request is allowed until it answers.
+    pub fn allow(&self, settings: &ProviderConfig) -> bool {
+        let mut circuits = CIRCUITS.lock().expect("circuits poisoned");
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(self.cooldown_seconds);
+        let circuit = circuits
+            .entry(settings.provider.to_string())
+            .or_insert(Circuit::Closed(0));
+        match *circuit {
+            Circuit::Closed(_) => true,
+            Circuit::Open(until) if until > now => false,
+            // a probe which never ended does not block the provider forever.
+            Circuit::HalfOpen(since) if since + cooldown > now => false,
+            Circuit::Open(_) | Circuit::HalfOpen(_) => {
+                *circuit = Circuit::HalfOpen(now);
+                true
+            }
+        }
+    }
+    /// the provider answered, even without cover.
+    pub fn success(&self, settings: &ProviderConfig) {
+        let mut circuits = CIRCUITS.lock().expect("circuits poisoned");
+        circuits.insert(settings.provider.to_string(), Circuit::Closed(0));
+    }
+    /// the provider could not be joined. Return true if the circuit is now open.
+    pub fn failure(&self, settings: &ProviderConfig) -> bool {
+        let mut circuits = CIRCUITS.lock().expect("circuits poisoned");
+        let open = Circuit::Open(Instant::now() + Duration::from_secs(self.cooldown_seconds));
+        let circuit = circuits
+            .entry(settings.provider.to_string())
+            .or_insert(Circuit::Closed(0));
+        *circuit = match *circuit {
+            Circuit::Closed(failures) if failures + 1 < self.threshold => {
+                Circuit::Closed(failures + 1)
+            }
+            _ => open,
+        };
+        matches!(circuit, Circuit::Open(_))
+    }
+}
+
+/// state of the circuits of the providers.
+pub fn circuits_status(providers: &[ProviderConfig]) -> Vec<CircuitStatus> {
+    let circuits = CIRCUITS.lock().expect("circuits poisoned");
+    let now = Instant::now();
+    providers
+        .iter()
+        .map(|settings| {
+            let provider = settings.provider.to_string();
+            let circuit = circuits
+                .get(&provider)
+                .copied()
+                .unwrap_or(Circuit::Closed(0));
+            let (state, failures, retry_in) = match circuit {
+                Circuit::Closed(failures) => ("closed", failures, None),
+                Circuit::Open(until) => ("open", 0, Some(until.saturating_duration_since(now))),
+                Circuit::HalfOpen(_) => ("half-open", 0, None),
+            };
+            CircuitStatus {
+                provider,
+                state,
+                failures,
+                retry_in: retry_in.map(|d| d.as_secs()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::provider_named;
+
+    fn state(settings: &ProviderConfig) -> &'static str {
+        circuits_status(std::slice::from_ref(settings))[0].state
+    }
+
+    #[test]
+    fn opens_after_threshold() {
+        let breaker = CircuitBreaker {
+            threshold: 2,
+            cooldown_seconds: 300,
+        };
+        let settings = provider_named("threshold");
+        assert!(breaker.allow(&settings));
+        assert!(!breaker.failure(&settings));
+        // an answer resets the count of failures.
+        breaker.success(&settings);
+        assert!(!breaker.failure(&settings));
+        assert!(breaker.failure(&settings));
+        assert_eq!(state(&settings), "open");
+        assert!(!breaker.allow(&settings));
+    }
+
+    #[test]
+    fn probe_after_cooldown() {
+        let breaker = CircuitBreaker {
+            threshold: 1,
+            cooldown_seconds: 0,
+        };
+        let settings = provider_named("cooldown");
+        assert!(breaker.failure(&settings));
+        assert!(breaker.allow(&settings));
+        assert_eq!(state(&settings), "half-open");
+        // the probe failing opens the circuit again.
+        assert!(breaker.failure(&settings));
+        assert_eq!(state(&settings), "open");
+        assert!(breaker.allow(&settings));
+        breaker.success(&settings);
+        assert_eq!(state(&settings), "closed");
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use tokio::task::spawn_blocking;
+
+use super::{Fetched, NotFound};
+
+/// patterns used if none are set in the configuration.
+pub fn default_patterns() -> Vec<String> {
+    ["jpg", "jpeg", "png", "webp", "tif", "tiff"]
+        .iter()
+        .map(|ext| ["{barcode}.", ext].concat())
+        .collect()
+}
+
+/// read the first file of the directory matching a pattern, compared without case.
+pub async fn cover(path: &Path, patterns: &[String], barcode: &str) -> Result<Fetched> {
+    let (path, patterns, barcode) = (path.to_path_buf(), patterns.to_vec(), barcode.to_string());
+    // the directory can be a slow network share, it must not block the runtime.
+    spawn_blocking(move || find(&path, &patterns, &barcode)).await?
+}
+
+fn find(path: &Path, patterns: &[String], barcode: &str) -> Result<Fetched> {
+    // a share not mounted is not a missing cover, the error is retried later.
+    let mut files = std::fs::read_dir(path)
+        .with_context(|| format!("directory {} can not be read", path.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.sort();
+    // glob compares the names without wildcards as they are written, the names are matched here.
+    let options = MatchOptions {
+        case_sensitive: false,
+        ..Default::default()
+    };
+    for pattern in patterns {
+        let pattern = Pattern::new(&pattern.replace("{barcode}", barcode))?;
+        let matching = files.iter().find(|file| {
+            file.is_file()
+                && file
+                    .file_name()
+                    .is_some_and(|name| pattern.matches_with(&name.to_string_lossy(), options))
+        });
+        if let Some(file) = matching {
+            return Ok(Fetched {
+                cover: std::fs::read(file)
+                    .with_context(|| format!("file {} can not be read", file.display()))?,
+                source: file.display().to_string(),
+            });
+        }
+    }
+    Err(NotFound.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    #[test]
+    fn files_by_barcode_and_extension() {
+        // brackets in the directory must not be read as a pattern.
+        let dir = std::env::temp_dir().join(format!("covers-api [{}]", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("9780306406157.JPG"), b"isbn13").unwrap();
+        write(dir.join("0306406152.webp"), b"isbn10").unwrap();
+        write(dir.join("036000291452.gif"), b"gif").unwrap();
+        let patterns = default_patterns();
+        let found = |barcode: &str| find(&dir, &patterns, barcode);
+        let isbn13 = found("9780306406157").unwrap();
+        assert_eq!(isbn13.cover, b"isbn13");
+        assert!(isbn13.source.ends_with("9780306406157.JPG"));
+        assert_eq!(found("0306406152").unwrap().cover, b"isbn10");
+        // extensions not in the patterns are not read.
+        assert!(found("036000291452").err().unwrap().is::<NotFound>());
+        assert!(found("96385074").err().unwrap().is::<NotFound>());
+        let custom = [String::from("{barcode}.gif")];
+        assert_eq!(find(&dir, &custom, "036000291452").unwrap().cover, b"gif");
+        remove_dir_all(&dir).unwrap();
+        // a directory missing is an error of the share, not a missing cover.
+        let missing = found("9780306406157").err().unwrap();
+        assert!(missing.is::<std::io::Error>());
+    }
+}
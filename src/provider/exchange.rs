@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+tokio::task_local! {
+    // requests of the call to a provider being made.
+    static EXCHANGE: RefCell<Exchange>;
+}
+
+/// what is recorded of the requests made by a call to a provider.
+#[derive(Default, Clone, Copy)]
+pub struct Exchange {
+    // status of the last response received.
+    pub status: Option<u16>,
+    // time spent waiting for the turn of the provider instead of requesting it.
+    pub waited: Duration,
+}
+
+/// make the call to a provider, with what was recorded of its requests.
+pub async fn recorded<F: Future>(call: F) -> (F::Output, Exchange) {
+    EXCHANGE
+        .scope(RefCell::new(Exchange::default()), async {
+            let output = call.await;
+            (output, EXCHANGE.with(|e| *e.borrow()))
+        })
+        .await
+}
+
+/// keep the status of a response of the provider.
+pub fn record_status(status: StatusCode) {
+    // requests made outside of a call to a provider are not recorded.
+    let _ = EXCHANGE.try_with(|e| e.borrow_mut().status = Some(status.as_u16()));
+}
+
+/// count a time waited before a request to the provider.
+pub fn record_wait(waited: Duration) {
+    let _ = EXCHANGE.try_with(|e| e.borrow_mut().waited += waited);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn each_call_has_its_own_record() {
+        let call = |status| async move {
+            record_status(StatusCode::OK);
+            record_wait(Duration::from_secs(1));
+            record_status(status);
+            record_wait(Duration::from_secs(2));
+        };
+        let ((), exchange) = recorded(call(StatusCode::NOT_FOUND)).await;
+        assert_eq!(exchange.status, Some(404));
+        assert_eq!(exchange.waited, Duration::from_secs(3));
+        let ((), exchange) = recorded(async {}).await;
+        assert_eq!((exchange.status, exchange.waited), (None, Duration::ZERO));
+        // nothing is recorded outside of a call.
+        record_status(StatusCode::OK);
+    }
+}
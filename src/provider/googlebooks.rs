@@ -0,0 +1,180 @@
+use anyhow::Result;
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+
+use super::limit::CheckRateLimit;
+use super::{base_url, download, request, Fetched, NotFound};
+use crate::config::ProviderConfig;
+
+#[derive(Deserialize)]
+struct Volumes {
+    #[serde(default)]
+    items: Vec<Volume>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Volume {
+    id: String,
+    volume_info: VolumeInfo,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VolumeInfo {
+    image_links: Option<ImageLinks>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageLinks {
+    extra_large: Option<String>,
+    large: Option<String>,
+    medium: Option<String>,
+    small: Option<String>,
+    thumbnail: Option<String>,
+    small_thumbnail: Option<String>,
+}
+
+impl ImageLinks {
+    fn largest(self) -> Option<String> {
+        self.extra_large
+            .or(self.large)
+            .or(self.medium)
+            .or(self.small)
+            .or(self.thumbnail)
+            .or(self.small_thumbnail)
+    }
+}
+
+/// search the volume by ISBN and download the largest image available.
+/// https://developers.google.com/books/docs/v1/using
+pub async fn cover(client: &Client, settings: &ProviderConfig, barcode: &str) -> Result<Fetched> {
+    let api = base_url(settings, "https://www.googleapis.com/books/v1");
+    let volumes: Volumes = with_key(
+        request(client, settings, [&api, "/volumes"].concat()).await?,
+        settings,
+    )
+    .query(&[("q", ["isbn:", barcode].concat())])
+    .send()
+    .await?
+    .check_rate_limit()?
+    .error_for_status()?
+    .json()
+    .await?;
+    let volume = volumes.items.into_iter().next().ok_or(NotFound)?;
+    // results of a search only include thumbnails, the volume itself has all the sizes.
+    let full = async {
+        with_key(
+            request(client, settings, [&api, "/volumes/", &volume.id].concat()).await?,
+            settings,
+        )
+        .send()
+        .await?
+        .check_rate_limit()?
+        .error_for_status()?
+        .json::<Volume>()
+        .await
+        .map_err(anyhow::Error::from)
+    }
+    .await;
+    let url = full
+        .ok()
+        .and_then(|v| v.volume_info.image_links)
+        .or(volume.volume_info.image_links)
+        .and_then(ImageLinks::largest)
+        .ok_or(NotFound)?;
+    download(client, settings, url).await
+}
+
+/// the API can be used without key but with a lower quota.
+/// The key is sent in a header so it is not part of the urls written in errors.
+fn with_key(builder: RequestBuilder, settings: &ProviderConfig) -> RequestBuilder {
+    match &settings.api_key {
+        Some(key) => builder.header("x-goog-api-key", key),
+        None => builder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::{Path, Query, State},
+        http::{HeaderMap, StatusCode},
+        routing::get,
+        Json, Router,
+    };
+    use reqwest::Url;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::provider::CoverProvider;
+
+    const ISBN: &str = "9780306406157";
+
+    /// search answering with the items given, the volume having every size and the images.
+    async fn mock(items: Value) -> ProviderConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route(
+                "/volumes",
+                get(
+                    |Query(query): Query<HashMap<String, String>>, headers: HeaderMap| async move {
+                        // the key is sent in a header, never in the url.
+                        if headers.get("x-goog-api-key").is_none_or(|k| k != "key")
+                            || query.contains_key("key")
+                        {
+                            return Err(StatusCode::FORBIDDEN);
+                        }
+                        assert_eq!(query["q"], ["isbn:", ISBN].concat());
+                        Ok(Json(json!({ "items": items })))
+                    },
+                ),
+            )
+            .route(
+                "/volumes/:id",
+                get(
+                    |Path(id): Path<String>, State(base): State<String>| async move {
+                        Json(json!({
+                            "id": id,
+                            "volumeInfo": { "imageLinks": {
+                                "thumbnail": format!("{base}/thumbnail"),
+                                "large": format!("{base}/large"),
+                            }},
+                        }))
+                    },
+                ),
+            )
+            .route("/thumbnail", get(|| async { "thumbnail" }))
+            .route("/large", get(|| async { "large" }))
+            .with_state(base.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        ProviderConfig {
+            base_url: Some(Url::parse(&base).unwrap()),
+            api_key: Some(String::from("key")),
+            ..ProviderConfig::new(CoverProvider::GoogleBooks)
+        }
+    }
+
+    #[tokio::test]
+    async fn largest_image_of_the_volume() {
+        let settings = mock(json!([{
+            "id": "volume",
+            "volumeInfo": { "imageLinks": { "thumbnail": "unused" } },
+        }]))
+        .await;
+        let fetched = cover(&Client::new(), &settings, ISBN).await.unwrap();
+        assert_eq!(fetched.cover, b"large");
+        assert!(fetched.source.ends_with("/large"));
+    }
+
+    #[tokio::test]
+    async fn no_volume_is_not_found() {
+        let settings = mock(json!([])).await;
+        let error = cover(&Client::new(), &settings, ISBN).await.err().unwrap();
+        assert!(error.is::<NotFound>());
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::limit::CheckRateLimit;
+use super::{base_url, download, request, Fetched, NotFound};
+use crate::config::ProviderConfig;
+
+#[derive(Deserialize)]
+struct Search {
+    response: SearchResponse,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    docs: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    identifier: String,
+}
+
+/// find the item of the ISBN with the advanced search and download its cover image.
+/// https://archive.org/developers/index-apis.html
+pub async fn cover(client: &Client, settings: &ProviderConfig, barcode: &str) -> Result<Fetched> {
+    let api = base_url(settings, "https://archive.org");
+    let search: Search = request(client, settings, [&api, "/advancedsearch.php"].concat())
+        .await?
+        .query(&[
+            ("q", ["isbn:", barcode].concat().as_str()),
+            ("fl[]", "identifier"),
+            ("rows", "1"),
+            ("output", "json"),
+        ])
+        .send()
+        .await?
+        .check_rate_limit()?
+        .error_for_status()?
+        .json()
+        .await?;
+    let item = search.response.docs.into_iter().next().ok_or(NotFound)?;
+    download(
+        client,
+        settings,
+        [&api, "/services/img/", &item.identifier].concat(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::{Path, Query},
+        routing::get,
+        Json, Router,
+    };
+    use reqwest::Url;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::provider::CoverProvider;
+
+    const ISBN: &str = "9780306406157";
+
+    /// search answering with the items given, and the image of every item.
+    async fn mock(docs: Value) -> ProviderConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route(
+                "/advancedsearch.php",
+                get(|Query(query): Query<HashMap<String, String>>| async move {
+                    assert_eq!(query["q"], ["isbn:", ISBN].concat());
+                    assert_eq!(query["output"], "json");
+                    Json(json!({ "response": { "docs": docs } }))
+                }),
+            )
+            .route(
+                "/services/img/:identifier",
+                get(|Path(identifier): Path<String>| async move { identifier }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        ProviderConfig {
+            base_url: Some(Url::parse(&base).unwrap()),
+            ..ProviderConfig::new(CoverProvider::InternetArchive)
+        }
+    }
+
+    #[tokio::test]
+    async fn image_of_the_first_item() {
+        let settings = mock(json!([{ "identifier": "item" }, { "identifier": "other" }])).await;
+        let fetched = cover(&Client::new(), &settings, ISBN).await.unwrap();
+        assert_eq!(fetched.cover, b"item");
+        assert!(fetched.source.ends_with("/services/img/item"));
+    }
+
+    #[tokio::test]
+    async fn no_item_is_not_found() {
+        let settings = mock(json!([])).await;
+        let error = cover(&Client::new(), &settings, ISBN).await.err().unwrap();
+        assert!(error.is::<NotFound>());
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::limit::CheckRateLimit;
+use super::{base_url, download, request, Fetched, NotFound};
+use crate::config::ProviderConfig;
+
+#[derive(Deserialize)]
+struct Response {
+    book: Book,
+}
+
+#[derive(Deserialize)]
+struct Book {
+    image: Option<String>,
+}
+
+/// get the book of the ISBN and download its image.
+/// https://isbndb.com/apidocs/v2
+pub async fn cover(client: &Client, settings: &ProviderConfig, barcode: &str) -> Result<Fetched> {
+    let api = base_url(settings, "https://api2.isbndb.com");
+    let key = settings
+        .api_key
+        .as_ref()
+        .context("ISBNdb requires an API key, set api_key_path_pass for this provider")?;
+    let response: Response = request(client, settings, [&api, "/book/", barcode].concat())
+        .await?
+        .header("Authorization", key)
+        .send()
+        .await?
+        .check_rate_limit()?
+        .error_for_status()?
+        .json()
+        .await?;
+    let url = response.book.image.ok_or(NotFound)?;
+    download(client, settings, url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::{Path, State},
+        http::{HeaderMap, StatusCode},
+        routing::get,
+        Json, Router,
+    };
+    use reqwest::Url;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::provider::CoverProvider;
+
+    const ISBN: &str = "9780306406157";
+
+    /// book of the ISBN with an image, other books without.
+    async fn mock() -> ProviderConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app =
+            Router::new()
+                .route(
+                    "/book/:isbn",
+                    get(
+                        |Path(isbn): Path<String>,
+                         State(base): State<String>,
+                         headers: HeaderMap| async move {
+                            // the key is sent in a header, never in the url.
+                            if headers.get("authorization").is_none_or(|k| k != "key") {
+                                return Err(StatusCode::UNAUTHORIZED);
+                            }
+                            let image = (isbn == ISBN).then(|| format!("{base}/cover"));
+                            Ok(Json(json!({ "book": { "isbn13": isbn, "image": image } })))
+                        },
+                    ),
+                )
+                .route("/cover", get(|| async { "cover" }))
+                .with_state(base.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        ProviderConfig {
+            base_url: Some(Url::parse(&base).unwrap()),
+            api_key: Some(String::from("key")),
+            ..ProviderConfig::new(CoverProvider::Isbndb)
+        }
+    }
+
+    #[tokio::test]
+    async fn image_of_the_book() {
+        let settings = mock().await;
+        let fetched = cover(&Client::new(), &settings, ISBN).await.unwrap();
+        assert_eq!(fetched.cover, b"cover");
+        assert!(fetched.source.ends_with("/cover"));
+    }
+
+    #[tokio::test]
+    async fn book_without_image_is_not_found() {
+        let settings = mock().await;
+        let error = cover(&Client::new(), &settings, "0306406152")
+            .await
+            .err()
+            .unwrap();
+        assert!(error.is::<NotFound>());
+    }
+
+    #[tokio::test]
+    async fn key_is_required() {
+        let settings = ProviderConfig {
+            api_key: None,
+            ..mock().await
+        };
+        assert!(cover(&Client::new(), &settings, ISBN).await.is_err());
+    }
+}
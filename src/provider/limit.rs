@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::time::sleep;
+
+use super::exchange::{record_status, record_wait};
+use crate::config::ProviderConfig;
+
+// pause of a provider answering 429 without Retry-After header.
+const DEFAULT_PAUSE: Duration = Duration::from_secs(60);
+
+// longest pause, a Retry-After far in the future is not followed.
+const MAX_PAUSE: Duration = Duration::from_secs(86400);
+
+// bucket of every provider, by name.
+static BUCKETS: Mutex<BTreeMap<String, Bucket>> = Mutex::new(BTreeMap::new());
+
+/// token bucket limiting the requests made to a provider.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct RateLimit {
+    // requests allowed per second on average.
+    pub per_second: f64,
+    // requests that can be made at once after the provider was not used for some time.
+    pub burst: u32,
+}
+
+/// the provider asked to stop sending requests for some time.
+#[derive(Error, Debug)]
+#[error("the provider is rate limiting requests")]
+pub struct RateLimited(pub Option<Duration>);
+
+/// the provider can not be used until the end of its pause.
+#[derive(Error, Debug)]
+#[error("the provider is paused")]
+pub struct Paused;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    paused_until: Option<Instant>,
+}
+
+/// wait for a token of the provider, fail if the provider is paused.
+pub async fn acquire(settings: &ProviderConfig) -> Result<(), Paused> {
+    loop {
+        let wait = {
+            let mut buckets = BUCKETS.lock().expect("rate limit buckets poisoned");
+            let now = Instant::now();
+            let bucket = buckets
+                .entry(settings.provider.to_string())
+                .or_insert(Bucket {
+                    tokens: settings.rate_limit.map(|r| r.burst as f64).unwrap_or(1.0),
+                    updated: now,
+                    paused_until: None,
+                });
+            if bucket.paused_until.is_some_and(|p| p > now) {
+                return Err(Paused);
+            }
+            let Some(limit) = settings.rate_limit else {
+                return Ok(());
+            };
+            bucket.tokens = (bucket.tokens
+                + now.duration_since(bucket.updated).as_secs_f64() * limit.per_second)
+                .min(limit.burst as f64);
+            bucket.updated = now;
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return Ok(());
+            }
+            Duration::from_secs_f64((1.0 - bucket.tokens) / limit.per_second)
+        };
+        sleep(wait).await;
+        record_wait(wait);
+    }
+}
+
+/// stop using the provider for the time asked, or a default time.
+pub fn pause(settings: &ProviderConfig, retry_after: Option<Duration>) {
+    let mut buckets = BUCKETS.lock().expect("rate limit buckets poisoned");
+    let now = Instant::now();
+    let bucket = buckets
+        .entry(settings.provider.to_string())
+        .or_insert(Bucket {
+            tokens: 0.0,
+            updated: now,
+            paused_until: None,
+        });
+    bucket.paused_until = Some(now + retry_after.unwrap_or(DEFAULT_PAUSE).min(MAX_PAUSE));
+}
+
+pub trait CheckRateLimit: Sized {
+    /// return an error if the provider answered 429, with the time to wait from the Retry-After header.
+    fn check_rate_limit(self) -> anyhow::Result<Self>;
+}
+
+impl CheckRateLimit for Response {
+    fn check_rate_limit(self) -> anyhow::Result<Self> {
+        record_status(self.status());
+        if self.status() != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(self);
+        }
+        // Retry-After is either a number of seconds or a date.
+        let retry_after = self
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| {
+                v.trim().parse().map(Duration::from_secs).ok().or_else(|| {
+                    DateTime::parse_from_rfc2822(v)
+                        .ok()
+                        .and_then(|d| (d.with_timezone(&Utc) - Utc::now()).to_std().ok())
+                })
+            });
+        Err(RateLimited(retry_after).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::provider_named;
+
+    #[tokio::test]
+    async fn burst_then_wait_for_a_token() {
+        let limit = RateLimit {
+            per_second: 20.0,
+            burst: 2,
+        };
+        let settings = ProviderConfig {
+            rate_limit: Some(limit),
+            ..provider_named("burst")
+        };
+        let start = Instant::now();
+        acquire(&settings).await.unwrap();
+        acquire(&settings).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(40));
+        acquire(&settings).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn paused_provider_is_refused() {
+        let settings = provider_named("paused");
+        acquire(&settings).await.unwrap();
+        pause(&settings, Some(Duration::from_secs(60)));
+        assert!(acquire(&settings).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn long_pause_does_not_overflow() {
+        let settings = provider_named("long pause");
+        pause(&settings, Some(Duration::MAX));
+        assert!(acquire(&settings).await.is_err());
+    }
+}
@@ -0,0 +1,1136 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::attempt::record_attempt;
+use crate::barcode::Barcode;
+use crate::config::{Config, ProviderConfig, Route, Strategy};
+#[cfg(feature = "openlibrary")]
+use crate::cover::set_fuzzy;
+use crate::cover::{cover_exist, set_original, update_table_image};
+use crate::image::{webp_encoding, write_cover};
+use crate::placeholder::CoverInfo;
+use anyhow::{bail, Result};
+pub use breaker::{circuits_status, CircuitBreaker};
+use deadpool_diesel::mysql::Object;
+use derive_more::Display;
+use exchange::recorded;
+use futures_util::future::join_all;
+pub use limit::RateLimit;
+use limit::{acquire, pause, CheckRateLimit, Paused, RateLimited};
+use quota::{add_call, quota_reached};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use template::Template;
+use thiserror::Error;
+use tokio::task::spawn_blocking;
+use tracing::{debug, info};
+
+mod breaker;
+mod directory;
+mod exchange;
+#[cfg(feature = "googlebooks")]
+mod googlebooks;
+#[cfg(feature = "internetarchive")]
+mod internetarchive;
+#[cfg(feature = "isbndb")]
+mod isbndb;
+mod limit;
+#[cfg(feature = "musicbrainz")]
+mod musicbrainz;
+#[cfg(feature = "openlibrary")]
+mod openlibrary;
+mod quota;
+mod template;
+
+/// Variants are always declared so the code stored in DB stays the same whatever features are enabled.
+#[derive(Clone, Deserialize, Serialize, Display, PartialEq)]
+pub enum CoverProvider {
+    OpenLibrary,
+    Manual,
+    GoogleBooks,
+    InternetArchive,
+    Isbndb,
+    Template(Template),
+    // files named by barcode in a local directory or network share.
+    #[display(fmt = "Directory {}", "path.display()")]
+    Directory {
+        path: PathBuf,
+        #[serde(default = "directory::default_patterns")]
+        pattern: Vec<String>,
+    },
+    MusicBrainz,
+}
+
+/// what is known about the product to find its cover.
+pub struct Product {
+    pub barcode: Barcode,
+    pub reference: Option<String>,
+    // categories of the product API, only asked if a provider is restricted to some categories.
+    pub categories: Vec<u32>,
+    // type of the product API, only asked if a route use it.
+    pub product_type: Option<u8>,
+}
+
+/// image sent by a provider.
+pub struct Fetched {
+    pub cover: Vec<u8>,
+    // url or path of the image.
+    pub source: String,
+}
+
+/// the provider answered that it does not have the cover.
+#[derive(Error, Debug)]
+#[error("the provider does not have a cover for this product")]
+pub struct NotFound;
+
+/// url of the provider API, without trailing slash.
+#[cfg(any(
+    feature = "googlebooks",
+    feature = "internetarchive",
+    feature = "isbndb",
+    feature = "musicbrainz",
+    feature = "openlibrary"
+))]
+pub(super) fn base_url(settings: &ProviderConfig, default: &str) -> String {
+    settings
+        .base_url
+        .as_ref()
+        .map(|u| u.as_str())
+        .unwrap_or(default)
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// GET request with the timeout of the provider, once a token of its rate limit is taken.
+pub(super) async fn request(
+    client: &Client,
+    settings: &ProviderConfig,
+    url: String,
+) -> Result<RequestBuilder, Paused> {
+    acquire(settings).await?;
+    Ok(client
+        .get(url)
+        .timeout(Duration::from_secs(settings.timeout_seconds)))
+}
+
+/// download the image at this url.
+pub(super) async fn download(
+    client: &Client,
+    settings: &ProviderConfig,
+    url: String,
+) -> Result<Fetched> {
+    Ok(Fetched {
+        cover: request(client, settings, url.clone())
+            .await?
+            .send()
+            .await?
+            .check_rate_limit()?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec(),
+        source: url,
+    })
+}
+
+/// provider of its own, for tests using the state kept by provider name.
+#[cfg(test)]
+pub fn provider_named(name: &str) -> ProviderConfig {
+    ProviderConfig::new(CoverProvider::Directory {
+        path: name.into(),
+        pattern: Vec::new(),
+    })
+}
+
+/// verify that every provider of the configuration can be used by this build.
+pub fn check_providers(providers: &[ProviderConfig]) -> Result<()> {
+    for (i, settings) in providers.iter().enumerate() {
+        let cp = &settings.provider;
+        // quotas, rate limits and attempts are kept by name, two templates need their own.
+        let name = cp.to_string();
+        if providers[..i]
+            .iter()
+            .any(|p| p.provider.to_string() == name)
+        {
+            bail!("provider {cp} is present twice in the configuration, give a different name to each template.");
+        }
+        if cp == &CoverProvider::Manual {
+            bail!("provider {cp} can not be used to retrieve covers, remove it from the configuration.");
+        }
+        if !cp.is_compiled() {
+            bail!(
+                "provider {cp} is present in the configuration but covers-api was compiled without the feature \"{}\"",
+                cp.feature()
+            );
+        }
+        if cp == &CoverProvider::Isbndb && settings.api_key_path_pass.is_none() {
+            bail!("provider {cp} requires an API key, set api_key_path_pass for it.");
+        }
+        if cp == &CoverProvider::MusicBrainz && settings.user_agent.is_none() {
+            bail!("provider {cp} requires a User-Agent identifying the application and a contact, set user_agent for it.");
+        }
+        if let Some(limit) = settings.rate_limit {
+            if !(limit.per_second.is_finite() && limit.per_second > 0.0) || limit.burst < 1 {
+                bail!("rate_limit of provider {cp} needs a positive per_second and a burst of at least 1.");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// verify that every provider named by the routes is configured.
+pub fn check_routes(routes: &[Route], providers: &[ProviderConfig]) -> Result<()> {
+    for route in routes {
+        for name in &route.providers {
+            if !providers.iter().any(|p| &p.provider.to_string() == name) {
+                bail!(
+                    "route {} uses the provider {name} which is not in the configuration",
+                    route.name
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Route {
+    /// every condition set is met by the product.
+    fn matches(&self, product: &Product) -> bool {
+        let forms = product.barcode.forms();
+        (self.barcode_prefixes.is_empty()
+            || self
+                .barcode_prefixes
+                .iter()
+                .any(|prefix| forms.iter().any(|f| f.starts_with(prefix.as_str()))))
+            && (self.categories.is_empty()
+                || self
+                    .categories
+                    .iter()
+                    .any(|c| product.categories.contains(c)))
+            && (self.product_types.is_empty()
+                || product
+                    .product_type
+                    .is_some_and(|t| self.product_types.contains(&t)))
+    }
+}
+
+/// name of the first route matching the product and its providers in order of priority.
+/// Products matching no route use every provider.
+pub fn route_for<'a>(config: &'a Config, product: &Product) -> (&'a str, Vec<&'a ProviderConfig>) {
+    let chain = |p: &&ProviderConfig| p.is_used_for(product);
+    match config.routes.iter().find(|r| r.matches(product)) {
+        Some(route) => (
+            &route.name,
+            route
+                .providers
+                .iter()
+                .filter_map(|name| {
+                    config
+                        .providers
+                        .iter()
+                        .find(|p| &p.provider.to_string() == name)
+                })
+                .filter(chain)
+                .collect(),
+        ),
+        None => ("default", config.providers.iter().filter(chain).collect()),
+    }
+}
+
+/// image received from a provider and accepted as a cover.
+pub struct Candidate {
+    pub provider: CoverProvider,
+    pub cover: Vec<u8>,
+    pub source: String,
+    pub info: CoverInfo,
+}
+
+/// answer of a provider for a product.
+pub enum Outcome {
+    Found(Candidate),
+    NotFound,
+    // the image sent is not a cover, with the reason.
+    Rejected(String),
+    // the provider could not answer, asking again later could work.
+    TransientError(String),
+    // the provider refused the request, asking again will give the same result.
+    PermanentError(String),
+    // the provider can not be asked now, the product must not be counted as missing a cover.
+    Paused,
+}
+
+/// outcome of a retrieval stored in the cover DB.
+#[derive(Clone, Copy, Display, Serialize, PartialEq)]
+pub enum OutcomeKind {
+    Found,
+    NotFound,
+    Rejected,
+    TransientError,
+    PermanentError,
+}
+
+impl OutcomeKind {
+    /// code of the outcome stored in the cover DB.
+    pub fn code(&self) -> u8 {
+        match self {
+            OutcomeKind::Found => 0,
+            OutcomeKind::NotFound => 1,
+            OutcomeKind::Rejected => 2,
+            OutcomeKind::TransientError => 3,
+            OutcomeKind::PermanentError => 4,
+        }
+    }
+    pub fn from_code(code: u8) -> Option<Self> {
+        [
+            OutcomeKind::Found,
+            OutcomeKind::NotFound,
+            OutcomeKind::Rejected,
+            OutcomeKind::TransientError,
+            OutcomeKind::PermanentError,
+        ]
+        .into_iter()
+        .find(|k| k.code() == code)
+    }
+}
+
+impl Outcome {
+    /// how much the outcome tells about the cover, the most useful is kept among the answers.
+    fn rank(&self) -> u8 {
+        match self {
+            Outcome::NotFound => 0,
+            Outcome::Rejected(_) => 1,
+            Outcome::PermanentError(_) => 2,
+            Outcome::TransientError(_) => 3,
+            Outcome::Paused => 4,
+            Outcome::Found(_) => 5,
+        }
+    }
+    fn most_useful(self, other: Self) -> Self {
+        if other.rank() > self.rank() {
+            other
+        } else {
+            self
+        }
+    }
+    /// kind and reason of the outcome to be stored, nothing is stored for a paused provider.
+    fn stored(&self) -> Option<(OutcomeKind, Option<String>)> {
+        match self {
+            Outcome::Found(_) => Some((OutcomeKind::Found, None)),
+            Outcome::NotFound => Some((OutcomeKind::NotFound, None)),
+            Outcome::Rejected(r) => Some((OutcomeKind::Rejected, Some(r.clone()))),
+            Outcome::TransientError(r) => Some((OutcomeKind::TransientError, Some(r.clone()))),
+            Outcome::PermanentError(r) => Some((OutcomeKind::PermanentError, Some(r.clone()))),
+            Outcome::Paused => None,
+        }
+    }
+}
+
+impl Candidate {
+    /// higher is better: resolution weighted by how close the aspect ratio is to the one expected.
+    /// The file size separates covers of the same resolution, less compressed is better.
+    fn score(&self, ratio: f32) -> f64 {
+        let pixels = self.info.width as f64 * self.info.height as f64;
+        let distance = (self.info.height as f64 / self.info.width as f64 - ratio as f64).abs();
+        pixels / (1.0 + distance * 2.0) + self.cover.len() as f64 / 1000.0
+    }
+}
+
+/// ask the providers of the routing rule of the product and store the result, the name of the rule is returned.
+pub async fn try_get_cover(
+    conn: &Object,
+    client: &Client,
+    config: &Config,
+    product: &Product,
+    product_id: u32,
+) -> Result<String> {
+    let (route, providers) = route_for(config, product);
+    info!("product {product_id} uses the routing rule {route}");
+    let outcomes =
+        match config.strategy {
+            Strategy::First => {
+                let mut outcomes = Vec::new();
+                for settings in providers {
+                    let outcome =
+                        provider_cover(conn, client, config, settings, product, product_id).await?;
+                    let found = matches!(outcome, Outcome::Found(_));
+                    outcomes.push(outcome);
+                    if found {
+                        break;
+                    }
+                }
+                outcomes
+            }
+            Strategy::Best => join_all(providers.into_iter().map(|settings| {
+                provider_cover(conn, client, config, settings, product, product_id)
+            }))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?,
+        };
+    let (outcomes, candidates): (Vec<_>, Vec<_>) = outcomes
+        .into_iter()
+        .partition(|o| !matches!(o, Outcome::Found(_)));
+    let candidate = found(candidates).max_by(|a, b| {
+        a.score(config.cover_ratio)
+            .total_cmp(&b.score(config.cover_ratio))
+    });
+    match candidate {
+        Some(candidate) => store_found(conn, config, product_id, candidate).await?,
+        None => {
+            let outcome = outcomes
+                .into_iter()
+                .fold(Outcome::NotFound, Outcome::most_useful);
+            store_missing(conn, config, product_id, outcome).await?
+        }
+    }
+    Ok(route.to_string())
+}
+
+/// write the cover found and mark the product as having one.
+async fn store_found(
+    conn: &Object,
+    config: &Config,
+    product_id: u32,
+    candidate: Candidate,
+) -> Result<()> {
+    let Candidate {
+        provider, cover, ..
+    } = candidate;
+    let (path_covers, sizes) = (config.path_covers.clone(), config.sizes.clone());
+    let webp = webp_encoding(&config.formats);
+    // decoding and resizing block the thread, the runtime must keep serving requests.
+    let original =
+        spawn_blocking(move || write_cover(&cover, product_id, &path_covers, &sizes, webp))
+            .await??;
+    update_table_image(
+        product_id,
+        conn,
+        config,
+        Some(provider),
+        Some(OutcomeKind::Found),
+        None,
+    )
+    .await?;
+    set_original(conn, product_id, original).await
+}
+
+/// store why no cover was found, unless the product already has one.
+async fn store_missing(
+    conn: &Object,
+    config: &Config,
+    product_id: u32,
+    outcome: Outcome,
+) -> Result<()> {
+    // a forced retrieval without result keeps the existing cover.
+    if cover_exist(conn, product_id).await? {
+        info!("no new cover found for product {product_id}, the existing one is kept");
+        return Ok(());
+    }
+    let Some((kind, reason)) = outcome.stored() else {
+        // the cover could exist on a paused provider, the product stays retrievable.
+        info!(
+            "no cover found for product {product_id} but a provider is paused, it will be retried"
+        );
+        return Ok(());
+    };
+    update_table_image(product_id, conn, config, None, Some(kind), reason).await?;
+    // result is ok even if no files has been changed. If using thiserror, result could be made more useful.
+    Ok(())
+}
+
+/// search the cover on OpenLibrary by title and author, for a product without barcode.
+/// A cover found is marked in the cover DB as coming from a fuzzy match.
+pub async fn try_get_cover_by_title(
+    conn: &Object,
+    client: &Client,
+    config: &Config,
+    title: &str,
+    author: Option<&str>,
+    product_id: u32,
+) -> Result<()> {
+    #[cfg(feature = "openlibrary")]
+    {
+        // use the settings of OpenLibrary if it is configured as a provider.
+        let settings = config
+            .providers
+            .iter()
+            .find(|p| p.provider == CoverProvider::OpenLibrary)
+            .cloned()
+            .unwrap_or_else(|| ProviderConfig::new(CoverProvider::OpenLibrary));
+        if !settings.enabled {
+            info!("product {product_id} has no barcode and OpenLibrary is disabled");
+            return Ok(());
+        }
+        // the product has no barcode, the attempt is recorded without one.
+        let outcome = ask_provider(conn, config, &settings, product_id, &[String::new()], |_| {
+            let settings = &settings;
+            async move {
+                let (fetched, similarity) =
+                    openlibrary::search(client, settings, &config.fuzzy_search, title, author)
+                        .await?
+                        .ok_or(NotFound)?;
+                debug!(
+                    "cover {} found for product {product_id} by title with a similarity of {similarity:.2}",
+                    fetched.source
+                );
+                Ok(fetched)
+            }
+        })
+        .await?;
+        match outcome {
+            Outcome::Found(candidate) => {
+                store_found(conn, config, product_id, candidate).await?;
+                set_fuzzy(conn, product_id).await
+            }
+            outcome => store_missing(conn, config, product_id, outcome).await,
+        }
+    }
+    #[cfg(not(feature = "openlibrary"))]
+    {
+        let _ = (conn, client, config, title, author, product_id);
+        bail!("fuzzy search needs the feature \"openlibrary\"")
+    }
+}
+
+/// ask every enabled provider at the same time for the cover of the product.
+/// Routes only choose the providers of automatic retrievals, the staff sees the covers of every provider.
+pub async fn all_candidates(
+    conn: &Object,
+    client: &Client,
+    config: &Config,
+    product: &Product,
+    product_id: u32,
+) -> Result<Vec<Candidate>> {
+    let outcomes = join_all(
+        config
+            .providers
+            .iter()
+            .filter(|p| p.is_used_for(product))
+            .map(|settings| provider_cover(conn, client, config, settings, product, product_id)),
+    )
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+    Ok(found(outcomes).collect())
+}
+
+fn found(outcomes: Vec<Outcome>) -> impl Iterator<Item = Candidate> {
+    outcomes.into_iter().filter_map(|o| match o {
+        Outcome::Found(candidate) => Some(candidate),
+        _ => None,
+    })
+}
+
+/// ask a provider for the cover of the product.
+async fn provider_cover(
+    conn: &Object,
+    client: &Client,
+    config: &Config,
+    settings: &ProviderConfig,
+    product: &Product,
+    product_id: u32,
+) -> Result<Outcome> {
+    ask_provider(
+        conn,
+        config,
+        settings,
+        product_id,
+        &forms_for(&settings.provider, product),
+        |barcode| async move {
+            settings
+                .provider
+                .method(client, settings, product, &barcode)
+                .await
+        },
+    )
+    .await
+}
+
+/// forms of the barcode sent to the provider, which can know the product by another form.
+/// A template whose url does not contain the barcode requests the same url whatever the form.
+fn forms_for(provider: &CoverProvider, product: &Product) -> Vec<String> {
+    let mut forms = product.barcode.forms();
+    if matches!(provider, CoverProvider::Template(t) if !t.use_barcode()) {
+        forms.truncate(1);
+    }
+    forms
+}
+
+/// make the calls of a provider for each key of the product, in the limits of its quota and circuit breaker.
+/// Every call is recorded with its key, the first cover found is returned.
+async fn ask_provider<F, Fut>(
+    conn: &Object,
+    config: &Config,
+    settings: &ProviderConfig,
+    product_id: u32,
+    keys: &[String],
+    call: F,
+) -> Result<Outcome>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<Fetched>>,
+{
+    let breaker = &config.circuit_breaker;
+    // a provider down is not asked until its cooldown expire.
+    if !breaker.allow(settings) {
+        return Ok(Outcome::Paused);
+    }
+    // the most useful answer among the forms of the barcode.
+    let mut outcome = Outcome::NotFound;
+    for barcode in keys {
+        for _ in 0..settings.max_attempts.max(1) {
+            // keep the remaining quota for the next days.
+            if quota_reached(conn, settings).await? {
+                return Ok(Outcome::Paused);
+            }
+            let start = Instant::now();
+            let (result, exchange) = recorded(call(barcode.clone())).await;
+            // the turn of the provider is not part of the time of its requests.
+            let duration = start.elapsed().saturating_sub(exchange.waited);
+            // the provider was paused before one of its requests, there is no answer to record.
+            if result.as_ref().is_err_and(|e| e.is::<Paused>()) {
+                return Ok(Outcome::Paused);
+            }
+            if settings.daily_quota.is_some() {
+                add_call(conn, settings).await?;
+            }
+            // a request failing without response has no status, even if the previous ones had.
+            let http_status = match result
+                .as_ref()
+                .map_err(|e| e.downcast_ref::<reqwest::Error>())
+            {
+                Err(Some(e)) => e.status().map(|s| s.as_u16()),
+                _ => exchange.status,
+            };
+            let answer = match result {
+                // a placeholder is not a cover.
+                Ok(Fetched { cover, source }) => match config.placeholder.check_cover(&cover) {
+                    Ok(info) => {
+                        debug!(
+                            "cover of {} for product {product_id} has the hash {:016x}",
+                            settings.provider, info.hash
+                        );
+                        Outcome::Found(Candidate {
+                            provider: settings.provider.clone(),
+                            cover,
+                            source,
+                            info,
+                        })
+                    }
+                    Err(reason) => {
+                        debug!(
+                            "cover of {} refused for product {product_id}: {reason}",
+                            settings.provider
+                        );
+                        Outcome::Rejected(reason.to_string())
+                    }
+                },
+                // stop asking the provider for the time it asked.
+                Err(e) if e.is::<RateLimited>() => {
+                    let retry_after = e.downcast_ref::<RateLimited>().and_then(|r| r.0);
+                    debug!(
+                        "{} is rate limiting, paused for {retry_after:?}",
+                        settings.provider
+                    );
+                    pause(settings, retry_after);
+                    Outcome::Paused
+                }
+                Err(e) => error_outcome(&e),
+            };
+            // a provider rate limiting has failed to answer this time.
+            let (kind, reason) = answer.stored().unwrap_or((
+                OutcomeKind::TransientError,
+                Some(RateLimited(None).to_string()),
+            ));
+            record_attempt(
+                conn,
+                product_id,
+                settings.provider.to_string(),
+                barcode,
+                http_status,
+                kind,
+                reason,
+                duration,
+            )
+            .await?;
+            match answer {
+                Outcome::Found(_) => {
+                    breaker.success(settings);
+                    return Ok(answer);
+                }
+                Outcome::Paused => return Ok(answer),
+                _ => (),
+            }
+            if let Outcome::TransientError(reason) = &answer {
+                debug!(
+                    "{} failed for product {product_id}: {reason}",
+                    settings.provider
+                );
+                // the provider is probably down, stop asking it if it keeps failing.
+                if breaker.failure(settings) {
+                    info!(
+                        "circuit of {} is open, it will not be asked for {}s",
+                        settings.provider, breaker.cooldown_seconds
+                    );
+                    return Ok(Outcome::Paused);
+                }
+                outcome = outcome.most_useful(answer);
+                continue;
+            }
+            // the provider answered, no need to ask again.
+            breaker.success(settings);
+            outcome = outcome.most_useful(answer);
+            break;
+        }
+    }
+    Ok(outcome)
+}
+
+/// outcome of an error returned by a provider.
+fn error_outcome(e: &anyhow::Error) -> Outcome {
+    if e.is::<NotFound>() {
+        return Outcome::NotFound;
+    }
+    // the reason is stored and shown to the staff, API keys sent in urls must not be part of it.
+    let reason = without_queries(&format!("{e:#}"));
+    match e.downcast_ref::<reqwest::Error>() {
+        // a transfer interrupted may succeed the next time.
+        Some(e)
+            if e.is_connect()
+                || e.is_timeout()
+                || e.is_request()
+                || e.is_body()
+                || e.is_decode() =>
+        {
+            Outcome::TransientError(reason)
+        }
+        Some(e) => match e.status() {
+            Some(StatusCode::NOT_FOUND | StatusCode::GONE) => Outcome::NotFound,
+            Some(s) if s.is_server_error() || s == StatusCode::REQUEST_TIMEOUT => {
+                Outcome::TransientError(reason)
+            }
+            _ => Outcome::PermanentError(reason),
+        },
+        // files of a directory provider which can not be read now, like a share not mounted.
+        None if e.is::<std::io::Error>() => Outcome::TransientError(reason),
+        None => Outcome::PermanentError(reason),
+    }
+}
+
+/// remove the query of every url in the message.
+fn without_queries(message: &str) -> String {
+    let mut redacted = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = [rest.find("http://"), rest.find("https://")]
+        .into_iter()
+        .flatten()
+        .min()
+    {
+        let end = rest[start..]
+            .find(|c: char| c.is_whitespace() || c == ')')
+            .map_or(rest.len(), |end| start + end);
+        let url = &rest[start..end];
+        redacted.push_str(&rest[..start]);
+        redacted.push_str(url.split_once('?').map_or(url, |(url, _)| url));
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+impl ProviderConfig {
+    /// the provider is enabled and the product is in one of its categories.
+    fn is_used_for(&self, product: &Product) -> bool {
+        self.enabled
+            && (self.categories.is_empty()
+                || self
+                    .categories
+                    .iter()
+                    .any(|c| product.categories.contains(c)))
+    }
+}
+
+impl CoverProvider {
+    async fn method(
+        &self,
+        client: &Client,
+        settings: &ProviderConfig,
+        product: &Product,
+        barcode: &str,
+    ) -> Result<Fetched> {
+        match self {
+            #[cfg(feature = "openlibrary")]
+            CoverProvider::OpenLibrary => openlibrary::cover(client, settings, barcode).await,
+            #[cfg(feature = "googlebooks")]
+            CoverProvider::GoogleBooks => googlebooks::cover(client, settings, barcode).await,
+            #[cfg(feature = "internetarchive")]
+            CoverProvider::InternetArchive => {
+                internetarchive::cover(client, settings, barcode).await
+            }
+            #[cfg(feature = "isbndb")]
+            CoverProvider::Isbndb => isbndb::cover(client, settings, barcode).await,
+            #[cfg(feature = "musicbrainz")]
+            CoverProvider::MusicBrainz => musicbrainz::cover(client, settings, barcode).await,
+            CoverProvider::Template(template) => {
+                template::cover(client, settings, template, product, barcode).await
+            }
+            CoverProvider::Directory { path, pattern } => {
+                directory::cover(path, pattern, barcode).await
+            }
+            CoverProvider::Manual => {
+                panic!("Manual should not be present in this method.\n This variant is only useful when using the manual upload of cover")
+            }
+            #[allow(unreachable_patterns)]
+            _ => bail!("provider {self} was not compiled"),
+        }
+    }
+    /// code of the provider stored in the cover DB.
+    pub fn code(&self) -> u8 {
+        match self {
+            CoverProvider::OpenLibrary => 0,
+            CoverProvider::Manual => 1,
+            CoverProvider::GoogleBooks => 2,
+            CoverProvider::InternetArchive => 3,
+            CoverProvider::Isbndb => 4,
+            CoverProvider::Template(_) => 5,
+            CoverProvider::Directory { .. } => 6,
+            CoverProvider::MusicBrainz => 7,
+        }
+    }
+    /// name of the provider stored with this code in the cover DB.
+    pub fn name_of_code(code: u8) -> Option<&'static str> {
+        let name = match code {
+            0 => "OpenLibrary",
+            1 => "Manual",
+            2 => "GoogleBooks",
+            3 => "InternetArchive",
+            4 => "Isbndb",
+            5 => "Template",
+            6 => "Directory",
+            7 => "MusicBrainz",
+            _ => return None,
+        };
+        Some(name)
+    }
+    /// cargo feature needed to use the provider.
+    fn feature(&self) -> &'static str {
+        match self {
+            CoverProvider::OpenLibrary => "openlibrary",
+            CoverProvider::GoogleBooks => "googlebooks",
+            CoverProvider::InternetArchive => "internetarchive",
+            CoverProvider::Isbndb => "isbndb",
+            CoverProvider::MusicBrainz => "musicbrainz",
+            CoverProvider::Manual
+            | CoverProvider::Template(_)
+            | CoverProvider::Directory { .. } => "",
+        }
+    }
+    fn is_compiled(&self) -> bool {
+        match self {
+            CoverProvider::OpenLibrary => cfg!(feature = "openlibrary"),
+            CoverProvider::GoogleBooks => cfg!(feature = "googlebooks"),
+            CoverProvider::InternetArchive => cfg!(feature = "internetarchive"),
+            CoverProvider::Isbndb => cfg!(feature = "isbndb"),
+            CoverProvider::MusicBrainz => cfg!(feature = "musicbrainz"),
+            CoverProvider::Manual
+            | CoverProvider::Template(_)
+            | CoverProvider::Directory { .. } => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(barcode: &str, categories: &[u32], product_type: Option<u8>) -> Product {
+        Product {
+            barcode: Barcode::parse(barcode).unwrap(),
+            reference: None,
+            categories: categories.to_vec(),
+            product_type,
+        }
+    }
+
+    fn route(name: &str, prefixes: &[&str], categories: &[u32], types: &[u8]) -> Route {
+        Route {
+            name: name.to_string(),
+            barcode_prefixes: prefixes.iter().map(|p| p.to_string()).collect(),
+            categories: categories.to_vec(),
+            product_types: types.to_vec(),
+            providers: vec![String::from("GoogleBooks"), String::from("OpenLibrary")],
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            providers: vec![
+                ProviderConfig::new(CoverProvider::OpenLibrary),
+                ProviderConfig::new(CoverProvider::GoogleBooks),
+                ProviderConfig::new(CoverProvider::MusicBrainz),
+            ],
+            routes: vec![
+                route("books", &["978", "979"], &[], &[]),
+                Route {
+                    providers: vec![String::from("MusicBrainz")],
+                    ..route("music", &[], &[12], &[])
+                },
+                route("games", &["4"], &[], &[1]),
+            ],
+            ..Config::default()
+        }
+    }
+
+    // name of the route and providers chosen for the product.
+    fn routed(config: &Config, product: &Product) -> (String, Vec<String>) {
+        let (route, providers) = route_for(config, product);
+        (
+            route.to_string(),
+            providers.iter().map(|p| p.provider.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn routes_by_prefix_category_and_type() {
+        let config = config();
+        // an ISBN-10 matches the prefix by its ISBN-13 form.
+        assert_eq!(
+            routed(&config, &product("0306406152", &[], None)),
+            (
+                "books".into(),
+                vec!["GoogleBooks".into(), "OpenLibrary".into()]
+            )
+        );
+        assert_eq!(
+            routed(&config, &product("4006381333931", &[12], None)),
+            ("music".into(), vec!["MusicBrainz".into()])
+        );
+        assert_eq!(
+            routed(&config, &product("4006381333931", &[], Some(1))).0,
+            "games"
+        );
+        // every condition of a route must be met.
+        assert_eq!(
+            routed(&config, &product("036000291452", &[], Some(1))).0,
+            "default"
+        );
+    }
+
+    #[test]
+    fn first_route_matching_is_used() {
+        let config = config();
+        assert_eq!(
+            routed(&config, &product("9780306406157", &[12], Some(1))).0,
+            "books"
+        );
+    }
+
+    #[test]
+    fn default_chain_without_route() {
+        let mut config = config();
+        config.providers[1].enabled = false;
+        assert_eq!(
+            routed(&config, &product("036000291452", &[], None)),
+            (
+                "default".into(),
+                vec!["OpenLibrary".into(), "MusicBrainz".into()]
+            )
+        );
+        // a disabled provider is removed from the chain of a route too.
+        assert_eq!(
+            routed(&config, &product("9780306406157", &[], None)).1,
+            ["OpenLibrary"]
+        );
+    }
+
+    #[test]
+    fn templates_without_barcode_are_asked_once() {
+        let template = |url: &str| {
+            CoverProvider::Template(Template {
+                name: String::from("test"),
+                url: url.to_string(),
+                headers: Default::default(),
+                json_path: None,
+                not_found_status: Vec::new(),
+            })
+        };
+        let book = product("9780306406157", &[], None);
+        assert_eq!(
+            forms_for(&template("https://example.net/{barcode}.jpg"), &book),
+            ["9780306406157", "0306406152"]
+        );
+        assert_eq!(
+            forms_for(&template("https://example.net/{isbn10}.jpg"), &book),
+            ["9780306406157"]
+        );
+        assert_eq!(
+            forms_for(&CoverProvider::OpenLibrary, &book),
+            ["9780306406157", "0306406152"]
+        );
+    }
+
+    /// error of the response with this status.
+    fn status_error(status: u16) -> anyhow::Error {
+        let response = axum::http::Response::builder()
+            .status(status)
+            .body("")
+            .unwrap();
+        reqwest::Response::from(response)
+            .error_for_status()
+            .unwrap_err()
+            .into()
+    }
+
+    /// server answering each connection with the raw response given, or never if it is empty.
+    async fn raw_server(response: &'static str) -> String {
+        use tokio::io::AsyncWriteExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/cover.jpg", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            // connections without answer are kept open until the end of the test.
+            let mut waiting = Vec::new();
+            while let Ok((mut stream, _)) = listener.accept().await {
+                if response.is_empty() {
+                    waiting.push(stream);
+                    continue;
+                }
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn errors_by_status() {
+        assert!(matches!(
+            error_outcome(&status_error(404)),
+            Outcome::NotFound
+        ));
+        assert!(matches!(
+            error_outcome(&status_error(410)),
+            Outcome::NotFound
+        ));
+        assert!(matches!(
+            error_outcome(&status_error(503)),
+            Outcome::TransientError(_)
+        ));
+        assert!(matches!(
+            error_outcome(&status_error(408)),
+            Outcome::TransientError(_)
+        ));
+        assert!(matches!(
+            error_outcome(&status_error(401)),
+            Outcome::PermanentError(_)
+        ));
+        assert!(matches!(
+            error_outcome(&status_error(400)),
+            Outcome::PermanentError(_)
+        ));
+    }
+
+    #[test]
+    fn errors_without_response() {
+        assert!(matches!(error_outcome(&NotFound.into()), Outcome::NotFound));
+        let io = std::io::Error::new(std::io::ErrorKind::NotConnected, "share not mounted");
+        assert!(matches!(
+            error_outcome(&io.into()),
+            Outcome::TransientError(_)
+        ));
+        assert!(matches!(
+            error_outcome(&anyhow::anyhow!("invalid settings")),
+            Outcome::PermanentError(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn transport_errors_are_transient() {
+        let client = Client::new();
+        // nothing listens on the port anymore.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+        let connect = client.get(closed).send().await.unwrap_err();
+        assert!(matches!(
+            error_outcome(&connect.into()),
+            Outcome::TransientError(_)
+        ));
+        let timeout = client
+            .get(raw_server("").await)
+            .timeout(Duration::from_millis(100))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error_outcome(&timeout.into()),
+            Outcome::TransientError(_)
+        ));
+        // the connection is closed before the end of the body.
+        let url = raw_server("HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\ncut").await;
+        let body = client
+            .get(url)
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error_outcome(&body.into()),
+            Outcome::TransientError(_)
+        ));
+    }
+
+    #[test]
+    fn queries_removed_from_reasons() {
+        assert_eq!(
+            without_queries(
+                "error sending request for url (https://api.example.net/v1/book?key=secret&q=978): timeout"
+            ),
+            "error sending request for url (https://api.example.net/v1/book): timeout"
+        );
+        assert_eq!(
+            without_queries("http://a.net/x?key=1 then https://b.net/y?token=2"),
+            "http://a.net/x then https://b.net/y"
+        );
+        assert_eq!(without_queries("no url ? here"), "no url ? here");
+    }
+
+    #[tokio::test]
+    async fn stored_reason_has_no_key() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = format!("http://{}/book?key=secret", listener.local_addr().unwrap());
+        drop(listener);
+        let error = Client::new().get(closed).send().await.unwrap_err();
+        let Outcome::TransientError(reason) = error_outcome(&error.into()) else {
+            panic!("a connection refused is transient");
+        };
+        assert!(!reason.contains("secret"), "{reason}");
+    }
+
+    #[test]
+    fn providers_have_distinct_names() {
+        let template = |name: &str| {
+            ProviderConfig::new(CoverProvider::Template(Template {
+                name: name.to_string(),
+                url: String::from("https://example.net/{barcode}.jpg"),
+                headers: Default::default(),
+                json_path: None,
+                not_found_status: Vec::new(),
+            }))
+        };
+        assert!(check_providers(&[template("distributor"), template("publisher")]).is_ok());
+        assert!(check_providers(&[template("distributor"), template("distributor")]).is_err());
+    }
+
+    #[test]
+    fn routes_use_configured_providers() {
+        let config = config();
+        assert!(check_routes(&config.routes, &config.providers).is_ok());
+        let routes = [Route {
+            providers: vec![String::from("Isbndb")],
+            ..route("unknown", &[], &[], &[])
+        }];
+        assert!(check_routes(&routes, &config.providers).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use reqwest::{header::USER_AGENT, Client, StatusCode};
+use serde::Deserialize;
+use tokio::{sync::Mutex, time::sleep_until};
+
+use super::exchange::record_wait;
+use super::limit::CheckRateLimit;
+use super::{base_url, request, Fetched, NotFound};
+use crate::config::ProviderConfig;
+
+// MusicBrainz allows one request per second for each client.
+// https://musicbrainz.org/doc/MusicBrainz_API/Rate_Limiting
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::const_new(None);
+
+// releases of the barcode for which the Cover Art Archive is asked.
+const MAX_RELEASES: usize = 3;
+
+#[derive(Deserialize)]
+struct Response {
+    releases: Vec<Release>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    id: String,
+}
+
+/// find the releases of the barcode on MusicBrainz and download the front cover from the Cover Art Archive.
+/// https://musicbrainz.org/doc/MusicBrainz_API/Search
+/// https://musicbrainz.org/doc/Cover_Art_Archive/API
+pub async fn cover(client: &Client, settings: &ProviderConfig, barcode: &str) -> Result<Fetched> {
+    cover_from(client, settings, barcode, "https://coverartarchive.org").await
+}
+
+/// cover of the barcode, the front images being asked to the archive given.
+async fn cover_from(
+    client: &Client,
+    settings: &ProviderConfig,
+    barcode: &str,
+    archive: &str,
+) -> Result<Fetched> {
+    let api = base_url(settings, "https://musicbrainz.org");
+    let user_agent = settings
+        .user_agent
+        .as_ref()
+        .context("MusicBrainz requires a User-Agent, set user_agent for this provider")?;
+    wait_turn().await;
+    let response: Response = request(client, settings, [&api, "/ws/2/release/"].concat())
+        .await?
+        .query(&[
+            ("query", ["barcode:", barcode].concat().as_str()),
+            ("fmt", "json"),
+        ])
+        .header(USER_AGENT, user_agent)
+        .send()
+        .await?
+        .check_rate_limit()?
+        .error_for_status()?
+        .json()
+        .await?;
+    // a barcode can be shared by several editions of the release, the first one with a cover is kept.
+    for release in response.releases.iter().take(MAX_RELEASES) {
+        let url = format!("{archive}/release/{}/front", release.id);
+        let response = request(client, settings, url.clone())
+            .await?
+            .header(USER_AGENT, user_agent)
+            .send()
+            .await?
+            .check_rate_limit()?;
+        // an edition without front cover is skipped, other errors are the ones of the archive.
+        if response.status() == StatusCode::NOT_FOUND {
+            continue;
+        }
+        return Ok(Fetched {
+            cover: response.error_for_status()?.bytes().await?.to_vec(),
+            source: url,
+        });
+    }
+    Err(NotFound.into())
+}
+
+/// wait until one second passed since the last request to MusicBrainz.
+async fn wait_turn() {
+    let start = Instant::now();
+    let mut last = LAST_REQUEST.lock().await;
+    if let Some(last) = *last {
+        sleep_until((last + Duration::from_secs(1)).into()).await;
+    }
+    *last = Some(Instant::now());
+    record_wait(start.elapsed());
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::{Path, Query},
+        http::{HeaderMap, StatusCode},
+        routing::get,
+        Json, Router,
+    };
+    use reqwest::Url;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::provider::CoverProvider;
+
+    const BARCODE: &str = "036000291452";
+
+    /// releases of the barcode, the first one without front image, and the archive on the same server.
+    async fn mock() -> (ProviderConfig, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route(
+                "/ws/2/release/",
+                get(
+                    |Query(query): Query<HashMap<String, String>>, headers: HeaderMap| async move {
+                        if headers.get("user-agent").is_none_or(|a| a != "covers-test") {
+                            return Err(StatusCode::FORBIDDEN);
+                        }
+                        let releases = if query["query"] == ["barcode:", BARCODE].concat() {
+                            json!([{ "id": "without" }, { "id": "with" }])
+                        } else {
+                            json!([{ "id": "without" }])
+                        };
+                        Ok(Json(json!({ "releases": releases })))
+                    },
+                ),
+            )
+            .route(
+                "/release/:id/front",
+                get(|Path(id): Path<String>| async move {
+                    match id.as_str() {
+                        "with" => Ok("front"),
+                        _ => Err(StatusCode::NOT_FOUND),
+                    }
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let settings = ProviderConfig {
+            base_url: Some(Url::parse(&base).unwrap()),
+            user_agent: Some(String::from("covers-test")),
+            ..ProviderConfig::new(CoverProvider::MusicBrainz)
+        };
+        (settings, base)
+    }
+
+    #[tokio::test]
+    async fn releases_without_front_are_skipped() {
+        let (settings, archive) = mock().await;
+        let client = Client::new();
+        let fetched = cover_from(&client, &settings, BARCODE, &archive)
+            .await
+            .unwrap();
+        assert_eq!(fetched.cover, b"front");
+        assert!(fetched.source.ends_with("/release/with/front"));
+        let error = cover_from(&client, &settings, "96385074", &archive)
+            .await
+            .err()
+            .unwrap();
+        assert!(error.is::<NotFound>());
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+use strsim::normalized_levenshtein;
+
+use super::limit::CheckRateLimit;
+use super::{base_url, download, request, Fetched};
+use crate::config::{FuzzySearch, ProviderConfig};
+
+pub async fn cover(client: &Client, settings: &ProviderConfig, barcode: &str) -> Result<Fetched> {
+    let url = [
+        &base_url(settings, "https://covers.openlibrary.org"),
+        "/b/isbn/",
+        barcode,
+        "-L.jpg?default=false",
+    ]
+    .concat();
+    download(client, settings, url).await
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    docs: Vec<Book>,
+}
+
+#[derive(Deserialize)]
+struct Book {
+    title: String,
+    #[serde(default)]
+    author_name: Vec<String>,
+    cover_i: Option<u64>,
+}
+
+/// search the book by title and author, return the cover of the most similar book if similar enough.
+/// The similarity of the book is returned with the cover.
+pub async fn search(
+    client: &Client,
+    settings: &ProviderConfig,
+    fuzzy: &FuzzySearch,
+    title: &str,
+    author: Option<&str>,
+) -> Result<Option<(Fetched, f64)>> {
+    let mut query = vec![
+        ("title", title),
+        ("fields", "title,author_name,cover_i"),
+        ("limit", "10"),
+    ];
+    if let Some(author) = author {
+        query.push(("author", author));
+    }
+    let url = [
+        fuzzy.search_url.as_str().trim_end_matches('/'),
+        "/search.json",
+    ]
+    .concat();
+    let response: SearchResponse = request(client, settings, url)
+        .await?
+        .query(&query)
+        .send()
+        .await?
+        .check_rate_limit()?
+        .error_for_status()?
+        .json()
+        .await?;
+    let best = response
+        .docs
+        .into_iter()
+        .filter_map(|book| {
+            let cover = book.cover_i?;
+            let mut similarity = similarity(title, &book.title);
+            // the author counts as much as the title when it is known.
+            if let Some(author) = author {
+                let author = book
+                    .author_name
+                    .iter()
+                    .map(|name| similarity_names(author, name))
+                    .fold(0.0, f64::max);
+                similarity = (similarity + author) / 2.0;
+            }
+            Some((cover, similarity))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    match best {
+        Some((cover, similarity)) if similarity >= fuzzy.min_similarity => {
+            let url = [
+                &base_url(settings, "https://covers.openlibrary.org"),
+                "/b/id/",
+                &cover.to_string(),
+                "-L.jpg?default=false",
+            ]
+            .concat();
+            Ok(Some((download(client, settings, url).await?, similarity)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// similarity between 0 and 1 of two titles, ignoring case, punctuation and subtitles.
+fn similarity(a: &str, b: &str) -> f64 {
+    let main_title = |t: &str| normalize(t.split([':', '(']).next().unwrap_or(t));
+    normalized_levenshtein(&main_title(a), &main_title(b))
+}
+
+/// similarity between 0 and 1 of two names, ignoring the order of the words.
+fn similarity_names(a: &str, b: &str) -> f64 {
+    let words = |n: &str| {
+        let mut words = normalize(n)
+            .split(' ')
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        words.sort();
+        words.join(" ")
+    };
+    normalized_levenshtein(&words(a), &words(b))
+}
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
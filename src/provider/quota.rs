@@ -0,0 +1,45 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use deadpool_diesel::mysql::Object;
+use diesel::{dsl::DuplicatedKeys, prelude::*};
+
+use crate::config::ProviderConfig;
+use crate::schema::provider_calls::dsl::{calls, day, provider, provider_calls};
+
+/// is the daily quota of calls of this provider used up ?
+pub async fn quota_reached(conn: &Object, settings: &ProviderConfig) -> Result<bool> {
+    let Some(quota) = settings.daily_quota else {
+        return Ok(false);
+    };
+    // providers defined by a template share a code, each has its own quota.
+    let name = settings.provider.to_string();
+    let today = Utc::now().date_naive();
+    let used: Option<u32> = conn
+        .interact(move |conn| {
+            provider_calls
+                .select(calls)
+                .filter(provider.eq(name).and(day.eq(today)))
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(|e| anyhow!(e.to_string()))??;
+    Ok(used.unwrap_or_default() >= quota)
+}
+
+/// count a call made today to the provider.
+pub async fn add_call(conn: &Object, settings: &ProviderConfig) -> Result<()> {
+    let name = settings.provider.to_string();
+    let today = Utc::now().date_naive();
+    conn.interact(move |conn| {
+        diesel::insert_into(provider_calls)
+            .values((provider.eq(name), day.eq(today), calls.eq(1)))
+            .on_conflict(DuplicatedKeys)
+            .do_update()
+            .set(calls.eq(calls + 1))
+            .execute(conn)
+    })
+    .await
+    .map_err(|e| anyhow!(e.to_string()))??;
+    Ok(())
+}
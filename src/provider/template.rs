@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use super::limit::CheckRateLimit;
+use super::{download, request, Fetched, NotFound, Product};
+use crate::config::ProviderConfig;
+
+/// provider defined entirely in the configuration file.
+#[derive(Clone, Deserialize, Serialize, PartialEq)]
+pub struct Template {
+    // name of the provider, used in logs.
+    pub name: String,
+    // url of the request, can contain placeholders {barcode}, {isbn10}, {isbn13}, {ref}
+    pub url: String,
+    // headers added to the request, values can contain the placeholder {api_key}
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    // JSONPath of the image url if the response is JSON. Without it, the response is the image.
+    pub json_path: Option<JsonPath>,
+    // status codes meaning the cover does not exist.
+    #[serde(default)]
+    pub not_found_status: Vec<u16>,
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Template {}", self.name)
+    }
+}
+
+impl Template {
+    /// does the url need the reference of the product ?
+    pub fn need_ref(&self) -> bool {
+        self.url.contains("{ref}")
+    }
+    /// does the url change with the form of the barcode ?
+    pub fn use_barcode(&self) -> bool {
+        self.url.contains("{barcode}")
+    }
+    /// replace the placeholders of the url. The cover can not be found if a value is missing.
+    fn url_for(&self, product: &Product, barcode: &str) -> Result<String, NotFound> {
+        let mut url = self.url.replace("{barcode}", barcode);
+        if url.contains("{isbn10}") {
+            url = url.replace("{isbn10}", &product.barcode.isbn10().ok_or(NotFound)?);
+        }
+        if url.contains("{isbn13}") {
+            url = url.replace("{isbn13}", &product.barcode.isbn13().ok_or(NotFound)?);
+        }
+        if url.contains("{ref}") {
+            url = url.replace("{ref}", product.reference.as_ref().ok_or(NotFound)?);
+        }
+        Ok(url)
+    }
+}
+
+/// request the url of the template, following the JSONPath if the response is JSON.
+pub async fn cover(
+    client: &Client,
+    settings: &ProviderConfig,
+    template: &Template,
+    product: &Product,
+    barcode: &str,
+) -> Result<Fetched> {
+    let url = template.url_for(product, barcode)?;
+    let mut req = request(client, settings, url.clone()).await?;
+    for (name, value) in &template.headers {
+        let value = match &settings.api_key {
+            Some(key) => value.replace("{api_key}", key),
+            None => value.to_owned(),
+        };
+        req = req.header(name, value);
+    }
+    let rep = req.send().await?.check_rate_limit()?;
+    if template.not_found_status.contains(&rep.status().as_u16()) {
+        return Err(NotFound.into());
+    }
+    let rep = rep.error_for_status()?;
+    let Some(path) = &template.json_path else {
+        return Ok(Fetched {
+            cover: rep.bytes().await?.to_vec(),
+            source: url,
+        });
+    };
+    let json: Value = rep.json().await?;
+    let url = path
+        .query(&json)
+        .first()
+        .and_then(Value::as_str)
+        .ok_or(NotFound)?
+        .to_string();
+    download(client, settings, url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::barcode::Barcode;
+
+    fn template(url: &str) -> Template {
+        Template {
+            name: String::from("test"),
+            url: url.to_string(),
+            headers: BTreeMap::new(),
+            json_path: None,
+            not_found_status: Vec::new(),
+        }
+    }
+
+    fn product(barcode: &str, reference: Option<&str>) -> Product {
+        Product {
+            barcode: Barcode::parse(barcode).unwrap(),
+            reference: reference.map(String::from),
+            categories: Vec::new(),
+            product_type: None,
+        }
+    }
+
+    #[test]
+    fn placeholders_of_the_url() {
+        let book = product("9780306406157", Some("REF-1"));
+        let url = template("https://example.net/{isbn10}/{isbn13}/{ref}/{barcode}.jpg")
+            .url_for(&book, "0306406152")
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://example.net/0306406152/9780306406157/REF-1/0306406152.jpg"
+        );
+        assert_eq!(
+            template("https://example.net/cover.jpg")
+                .url_for(&book, "9780306406157")
+                .unwrap(),
+            "https://example.net/cover.jpg"
+        );
+    }
+
+    #[test]
+    fn missing_values_are_not_found() {
+        // no ISBN-10 for a 979 book, no ISBN at all for other products, no reference.
+        let book = product("9791032305690", None);
+        assert!(template("https://example.net/{isbn10}")
+            .url_for(&book, "")
+            .is_err());
+        assert!(template("https://example.net/{ref}")
+            .url_for(&book, "")
+            .is_err());
+        let other = product("4006381333931", None);
+        assert!(template("https://example.net/{isbn13}")
+            .url_for(&other, "")
+            .is_err());
+    }
+}
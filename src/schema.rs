@@ -1,9 +1,47 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    cover_attempts (id) {
+        id -> Unsigned<Bigint>,
+        product_id -> Unsigned<Integer>,
+        #[max_length = 255]
+        provider -> Varchar,
+        #[max_length = 32]
+        barcode -> Varchar,
+        tried_at -> Datetime,
+        http_status -> Nullable<Unsigned<Smallint>>,
+        outcome -> Unsigned<Tinyint>,
+        error -> Nullable<Text>,
+        duration_ms -> Unsigned<Integer>,
+    }
+}
+
 diesel::table! {
     covers (id) {
         id -> Unsigned<Integer>,
         last_try -> Datetime,
         provider -> Nullable<Unsigned<Tinyint>>,
+        failure -> Nullable<Text>,
+        fuzzy -> Bool,
+        outcome -> Nullable<Unsigned<Tinyint>>,
+        attempts -> Unsigned<Integer>,
+        next_try_at -> Nullable<Datetime>,
+        #[max_length = 64]
+        original_type -> Nullable<Varchar>,
+        original_width -> Nullable<Unsigned<Integer>>,
+        original_height -> Nullable<Unsigned<Integer>>,
+        #[max_length = 255]
+        provider_name -> Nullable<Varchar>,
     }
 }
+
+diesel::table! {
+    provider_calls (provider, day) {
+        #[max_length = 255]
+        provider -> Varchar,
+        day -> Date,
+        calls -> Unsigned<Integer>,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(cover_attempts, covers, provider_calls,);